@@ -1,11 +1,27 @@
 use std::{
+    collections::HashSet,
     path::PathBuf,
-    sync::{Arc, Mutex},
+    sync::{mpsc::Sender, Arc, Mutex},
 };
 
+use git2::Delta;
 use notify::RecommendedWatcher;
 
-use crate::git::{self, commit, commit::Commit, stats::Stats, Diff};
+use crate::git::{
+    self,
+    blame::{BlameLine, BlameTarget},
+    branch::{self, Branch},
+    commit::{self, Author, Commit, FileCommit},
+    conflict::{self, ConflictFile},
+    discard::Discarded,
+    graph::Graph,
+    revision::RevisionRange,
+    search::Query,
+    settings::DiffSettings,
+    stash::{self, Stash},
+    stats::Stats,
+    Binary, Diff,
+};
 
 #[derive(Default)]
 pub struct ControlData {
@@ -13,12 +29,93 @@ pub struct ControlData {
     pub error_information: String,
     pub diff_type: DiffType,
     pub selected_diff: PathBuf,
+    /// Index into the lines of the selected diff to scroll to once it is shown.
+    pub scroll_to_line: Option<usize>,
     pub should_refresh: Arc<Mutex<bool>>,
     pub search_string: String,
+    pub ref_filter: Option<RefFilter>,
+    /// Shows the history of a single file in the log.
+    pub file_log: Option<FileLogSelection>,
     pub profiler: bool,
     pub log_open: bool,
+    pub stash_open: bool,
+    pub branch_open: bool,
+    /// Name for a new branch.
+    pub branch_name: String,
+    /// Commit a new branch starts at, HEAD if `None`.
+    pub branch_start: Option<String>,
+    /// Local branch being renamed and its new name.
+    pub branch_rename: Option<(String, String)>,
+    /// Branch waiting for the delete to be confirmed and whether it is merged.
+    pub branch_to_delete: Option<(String, bool)>,
+    pub stash_message: String,
+    pub stash_untracked: bool,
+    /// Stash waiting for the drop to be confirmed.
+    pub stash_to_drop: Option<String>,
+    pub image_mode: ImageMode,
+    /// Position of the swipe divider and opacity of the new image in onion skin mode.
+    pub image_blend: f32,
+    pub commit_message: String,
+    pub commit_amend: bool,
+    pub commit_skip_hooks: bool,
+    /// Commit waiting to be created in the background.
+    pub commit_request: Option<CommitRequest>,
+    /// Hooks can take a while, the message stays locked until the commit is done.
+    pub committing: bool,
     pub font_license: String,
     pub show_about_dialog: bool,
+    pub load_options: LoadOptions,
+    pub split_view: bool,
+    /// Draws tabs, trailing spaces and carriage returns.
+    pub show_whitespace: bool,
+    pub show_blame: bool,
+    pub revision_from: String,
+    pub revision_to: String,
+    pub revision_merge_base: bool,
+    pub selected_lines: Vec<usize>,
+    pub discarded: Vec<Discarded>,
+    pub save_discarded: bool,
+    pub show_discarded: bool,
+}
+
+impl ControlData {
+    pub fn refresh(&self) {
+        if let Ok(mut should_refresh) = self.should_refresh.lock() {
+            *should_refresh = true;
+        }
+    }
+
+    pub fn show_error(&mut self, error: String) {
+        self.error_information = error;
+        self.show_err_dialog = true;
+    }
+
+    /// Refreshes after an action changed the repository, the selected lines belong to the old
+    /// diff. Shows the error prefixed with `error` if the action failed.
+    pub fn finish(&mut self, result: anyhow::Result<()>, error: &str) {
+        match result {
+            Ok(()) => {
+                self.selected_lines.clear();
+                self.refresh();
+            }
+            Err(e) => self.show_error(format!("{error}: {e}")),
+        }
+    }
+}
+
+/// How the two sides of a changed image are compared.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ImageMode {
+    #[default]
+    SideBySide,
+    Swipe,
+    OnionSkin,
+}
+
+/// Limits the log to the history of a branch or tag.
+pub struct RefFilter {
+    pub name: String,
+    pub ids: HashSet<String>,
 }
 
 #[derive(Clone)]
@@ -26,8 +123,207 @@ pub struct AppData {
     pub project_path: String,
     pub modified_diff_data: DiffData,
     pub staged_diff_data: DiffData,
+    /// The range the revision diff was loaded for.
+    pub revision_range: Option<RevisionRange>,
+    pub revision_diff_data: Option<DiffData>,
+    pub commit_diff: Option<CommitDiff>,
+    /// The checked out branch, or a description of the detached HEAD.
+    pub head: String,
+    pub branches: Vec<Branch>,
+    pub stashes: Vec<Stash>,
+    pub conflicts: Vec<ConflictFile>,
+    pub stash_diff: Option<StashDiff>,
+    /// Identity for new commits, `None` if the git config has none.
+    pub author: Option<Author>,
+    /// Identifies the history the log should show, see `commit::log_key`.
+    pub log_key: String,
+    /// Parts that failed to load, everything else is still shown.
+    pub errors: Vec<String>,
+}
+
+/// The commit history, loaded page by page in the background.
+#[derive(Default)]
+pub struct Log {
+    pub key: String,
     pub commits: Vec<Commit>,
+    pub graph: Graph,
+    pub complete: bool,
+    /// Whether a page has been requested and not arrived yet.
+    pub loading: bool,
+    /// Asks the loading thread for the next page.
+    pub more: Option<Sender<()>>,
 }
+
+impl Log {
+    pub fn new(key: String, more: Sender<()>) -> Log {
+        Log {
+            key,
+            loading: true,
+            more: Some(more),
+            ..Default::default()
+        }
+    }
+
+    pub fn load_more(&mut self) {
+        if self.complete || self.loading {
+            return;
+        }
+
+        if let Some(more) = &self.more {
+            self.loading = more.send(()).is_ok();
+        }
+    }
+
+    pub fn append(&mut self, page: LogPage) {
+        if page.key != self.key {
+            return;
+        }
+
+        self.graph.extend(&page.commits);
+        self.commits.extend(page.commits);
+        self.complete = page.complete;
+        self.loading = false;
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub struct FileLogSelection {
+    pub path: PathBuf,
+    /// Continue with the old path of renamed files.
+    pub follow: bool,
+}
+
+/// The commits that changed a file, streamed in from the background.
+pub struct FileLog {
+    pub selection: FileLogSelection,
+    pub commits: Vec<FileCommit>,
+    pub complete: bool,
+    /// The loading thread stops once this is dropped.
+    _running: Sender<()>,
+}
+
+impl FileLog {
+    pub fn new(selection: FileLogSelection, running: Sender<()>) -> FileLog {
+        FileLog {
+            selection,
+            commits: Vec::new(),
+            complete: false,
+            _running: running,
+        }
+    }
+
+    pub fn append(&mut self, page: FileLogPage) {
+        if page.selection != self.selection {
+            return;
+        }
+
+        self.commits.extend(page.commits);
+        self.complete = page.complete;
+    }
+}
+
+/// The file and version of it the blame is shown for.
+#[derive(Clone, PartialEq)]
+pub struct BlameSelection {
+    pub file: PathBuf,
+    pub target: BlameTarget,
+}
+
+pub struct Blame {
+    pub selection: BlameSelection,
+    /// Empty until loaded or if the file can't be blamed.
+    pub lines: Vec<Option<BlameLine>>,
+}
+
+pub struct FileLogPage {
+    pub selection: FileLogSelection,
+    pub commits: Vec<FileCommit>,
+    pub complete: bool,
+}
+
+#[derive(Clone, PartialEq)]
+pub struct SearchSelection {
+    pub text: String,
+    pub all_branches: bool,
+}
+
+/// Results of a log search, streamed in from the background.
+pub struct Search {
+    pub selection: SearchSelection,
+    pub query: Result<Query, String>,
+    pub commits: Vec<Commit>,
+    pub complete: bool,
+    /// The searching thread stops once this is dropped.
+    _running: Sender<()>,
+}
+
+impl Search {
+    pub fn new(
+        selection: SearchSelection,
+        query: Result<Query, String>,
+        running: Sender<()>,
+    ) -> Search {
+        Search {
+            selection,
+            complete: query.is_err(),
+            query,
+            commits: Vec::new(),
+            _running: running,
+        }
+    }
+
+    pub fn append(&mut self, page: SearchPage) {
+        if page.selection != self.selection {
+            return;
+        }
+
+        self.commits.extend(page.commits);
+        self.complete = page.complete;
+    }
+}
+
+pub struct SearchPage {
+    pub selection: SearchSelection,
+    pub commits: Vec<Commit>,
+    pub complete: bool,
+}
+
+pub struct LogPage {
+    pub key: String,
+    pub commits: Vec<Commit>,
+    pub complete: bool,
+}
+
+/// Everything besides the path that decides what gets loaded from the repository.
+#[derive(Clone, Default)]
+pub struct LoadOptions {
+    pub revision_range: Option<RevisionRange>,
+    pub commit: Option<CommitSelection>,
+    /// Id of the stash to show.
+    pub stash: Option<String>,
+    pub diff_settings: DiffSettings,
+    /// Show all local branches and remote-tracking refs in the log, not only HEAD.
+    pub log_all_branches: bool,
+}
+
+#[derive(Clone, PartialEq)]
+pub struct CommitSelection {
+    pub id: String,
+    pub parent: usize,
+}
+
+#[derive(Clone)]
+pub struct CommitDiff {
+    pub commit: Commit,
+    pub diff_data: DiffData,
+}
+
+#[derive(Clone)]
+pub struct StashDiff {
+    pub stash: Stash,
+    pub diff_data: DiffData,
+}
+
 #[derive(Clone)]
 pub struct DiffData {
     pub diffs: Vec<Diff>,
@@ -36,6 +332,26 @@ pub struct DiffData {
 }
 
 impl DiffData {
+    fn new(diffs: Vec<Diff>, stats: Stats) -> DiffData {
+        let file_tree = Tree::new(
+            diffs
+                .iter()
+                .map(|d| File {
+                    path: d.file_name(),
+                    status: d.status,
+                    old_path: d.is_rename().then(|| d.old_file_name()),
+                    similarity: d.similarity,
+                    binary: d.binary,
+                })
+                .collect(),
+        );
+        DiffData {
+            diffs,
+            stats,
+            file_tree,
+        }
+    }
+
     pub fn get_diff(&self, name: &PathBuf) -> Option<Diff> {
         for diff in &self.diffs {
             if diff.file_name() == *name {
@@ -52,12 +368,18 @@ pub enum DiffType {
     #[default]
     Modified,
     Staged,
+    Revision,
+    Commit,
+    Stash,
 }
 impl DiffType {
     pub fn label_text(&self) -> String {
         match self {
             DiffType::Modified => "Modified".to_string(),
             DiffType::Staged => "Staged".to_string(),
+            DiffType::Revision => "Revisions".to_string(),
+            DiffType::Commit => "Commit".to_string(),
+            DiffType::Stash => "Stash".to_string(),
         }
     }
 }
@@ -65,45 +387,173 @@ impl DiffType {
 pub enum AppDataCreationError {
     Parsing,
     Commits,
+    Commit,
+    Stash,
+}
+
+/// Falls back to the default value and collects the error, so the rest can still be loaded.
+fn or_report<T: Default>(result: anyhow::Result<T>, error: &str, errors: &mut Vec<String>) -> T {
+    result.unwrap_or_else(|e| {
+        errors.push(format!("{error}: {e}"));
+        T::default()
+    })
 }
 
 impl AppData {
-    pub fn from_pathbuf(path: PathBuf) -> Result<AppData, AppDataCreationError> {
+    pub fn from_pathbuf(
+        path: PathBuf,
+        load_options: LoadOptions,
+    ) -> Result<AppData, AppDataCreationError> {
         let project_path = path
             .to_str()
             .ok_or(AppDataCreationError::Parsing)?
             .to_owned();
         let (modified_diffs, modified_stats) =
-            git::get_diffs(&project_path).map_err(|_| AppDataCreationError::Parsing)?;
+            git::get_diffs(&project_path, &load_options.diff_settings)
+                .map_err(|_| AppDataCreationError::Parsing)?;
 
         let (staged_diffs, staged_stats) =
-            git::get_staged_diffs(&project_path).map_err(|_| AppDataCreationError::Parsing)?;
+            git::get_staged_diffs(&project_path, &load_options.diff_settings)
+                .map_err(|_| AppDataCreationError::Parsing)?;
+
+        let mut errors = Vec::new();
+
+        // the revisions might have been deleted or rewritten in the meantime
+        let revision_range = load_options.revision_range;
+        let revision_diff_data = match &revision_range {
+            Some(range) => {
+                match git::get_revision_diffs(&project_path, range, &load_options.diff_settings) {
+                    Ok((revision_diffs, revision_stats)) => {
+                        Some(DiffData::new(revision_diffs, revision_stats))
+                    }
+                    Err(e) => {
+                        errors.push(format!("Error loading revision range {range}: {e}"));
+                        None
+                    }
+                }
+            }
+            None => None,
+        };
 
-        let modified_diff_data = DiffData {
-            diffs: modified_diffs.clone(),
-            stats: modified_stats,
-            file_tree: Tree::new(modified_diffs.iter().map(|d| d.file_name()).collect()),
+        let commit_diff = match load_options.commit {
+            Some(selection) => {
+                let commit = commit::get_commit(&project_path, &selection.id)
+                    .map_err(|_| AppDataCreationError::Commit)?;
+                let (commit_diffs, commit_stats) = git::get_commit_diffs(
+                    &project_path,
+                    &selection.id,
+                    selection.parent,
+                    &load_options.diff_settings,
+                )
+                .map_err(|_| AppDataCreationError::Commit)?;
+                Some(CommitDiff {
+                    commit,
+                    diff_data: DiffData::new(commit_diffs, commit_stats),
+                })
+            }
+            None => None,
         };
 
-        let staged_diff_data = DiffData {
-            diffs: staged_diffs.clone(),
-            stats: staged_stats,
-            file_tree: Tree::new(staged_diffs.iter().map(|d| d.file_name()).collect()),
+        // the diffs are still worth showing without these
+        let conflicts = or_report(
+            conflict::get_conflicts(&project_path),
+            "Error loading conflicts",
+            &mut errors,
+        );
+        let head = or_report(
+            branch::current(&project_path),
+            "Error loading HEAD",
+            &mut errors,
+        );
+        let branches = or_report(
+            branch::get_branches(&project_path),
+            "Error loading branches",
+            &mut errors,
+        );
+        let stashes = or_report(
+            stash::get_stashes(&project_path),
+            "Error loading stashes",
+            &mut errors,
+        );
+
+        // the stash might have been popped or dropped in the meantime
+        let stash_diff = match load_options
+            .stash
+            .and_then(|id| stashes.iter().find(|s| s.id == id))
+        {
+            Some(stash) => {
+                let (stash_diffs, stash_stats) =
+                    git::get_stash_diffs(&project_path, &stash.id, &load_options.diff_settings)
+                        .map_err(|_| AppDataCreationError::Stash)?;
+                Some(StashDiff {
+                    stash: stash.clone(),
+                    diff_data: DiffData::new(stash_diffs, stash_stats),
+                })
+            }
+            None => None,
         };
 
-        let commits = commit::get_log(&project_path).map_err(|_| AppDataCreationError::Commits)?;
+        let modified_diff_data = DiffData::new(modified_diffs, modified_stats);
+        let staged_diff_data = DiffData::new(staged_diffs, staged_stats);
+
+        let author = commit::author(&project_path).ok();
+
+        let log_key = commit::log_key(&project_path, load_options.log_all_branches)
+            .map_err(|_| AppDataCreationError::Commits)?;
 
         Ok(AppData {
             project_path,
             modified_diff_data,
             staged_diff_data,
-            commits,
+            revision_range,
+            revision_diff_data,
+            commit_diff,
+            head,
+            branches,
+            stashes,
+            conflicts,
+            stash_diff,
+            author,
+            log_key,
+            errors,
         })
     }
+
+    pub fn diff_data(&self, diff_type: &DiffType) -> Option<&DiffData> {
+        match diff_type {
+            DiffType::Modified => Some(&self.modified_diff_data),
+            DiffType::Staged => Some(&self.staged_diff_data),
+            DiffType::Revision => self.revision_diff_data.as_ref(),
+            DiffType::Commit => self.commit_diff.as_ref().map(|c| &c.diff_data),
+            DiffType::Stash => self.stash_diff.as_ref().map(|s| &s.diff_data),
+        }
+    }
+
+    pub fn diff_data_mut(&mut self, diff_type: &DiffType) -> Option<&mut DiffData> {
+        match diff_type {
+            DiffType::Modified => Some(&mut self.modified_diff_data),
+            DiffType::Staged => Some(&mut self.staged_diff_data),
+            DiffType::Revision => self.revision_diff_data.as_mut(),
+            DiffType::Commit => self.commit_diff.as_mut().map(|c| &mut c.diff_data),
+            DiffType::Stash => self.stash_diff.as_mut().map(|s| &mut s.diff_data),
+        }
+    }
 }
+#[derive(Debug, Clone)]
+pub struct CommitRequest {
+    pub message: String,
+    pub amend: bool,
+    pub run_hooks: bool,
+}
+
 pub enum Message {
     UpdateAppData(AppData),
+    UpdateLog(LogPage),
+    UpdateFileLog(FileLogPage),
+    UpdateSearch(SearchPage),
+    UpdateBlame(BlameSelection, Vec<Option<BlameLine>>),
     UpdateWatcher(RecommendedWatcher),
+    FinishCommit(anyhow::Result<()>),
     ShowError(String),
 }
 
@@ -119,6 +569,11 @@ pub struct Tree {
 #[derive(Debug, Clone, PartialEq)]
 pub struct File {
     pub path: PathBuf,
+    pub status: Delta,
+    /// Where a renamed or copied file came from.
+    pub old_path: Option<PathBuf>,
+    pub similarity: u16,
+    pub binary: Option<Binary>,
 }
 
 impl File {
@@ -128,7 +583,7 @@ impl File {
 }
 
 impl Tree {
-    fn new(paths: Vec<PathBuf>) -> Self {
+    fn new(files: Vec<File>) -> Self {
         let mut tree = Tree {
             nodes: vec![],
             files: vec![],
@@ -137,27 +592,28 @@ impl Tree {
             id: 0,
         };
 
-        for path in paths {
-            tree.add(path, 0, 1);
+        for file in files {
+            tree.add(file, 0, 1);
         }
 
         tree
     }
 
-    fn add(&mut self, path: PathBuf, depth: usize, id: u64) {
+    fn add(&mut self, file: File, depth: usize, id: u64) {
         // top level
-        if path.components().count() == 1 {
-            self.files.push(File { path });
+        if file.path.components().count() == 1 {
+            self.files.push(file);
             return;
         }
 
         // deepest level
-        if path.components().count() == depth + 1 {
-            self.files.push(File { path });
+        if file.path.components().count() == depth + 1 {
+            self.files.push(file);
             return;
         }
 
-        let name = path
+        let name = file
+            .path
             .components()
             .nth(depth)
             .unwrap()
@@ -169,7 +625,7 @@ impl Tree {
         // do we already have a tree for this?
         for node in &mut self.nodes {
             if node.name == name {
-                node.add(path.clone(), depth + 1, id + 1);
+                node.add(file, depth + 1, id + 1);
                 return;
             }
         }
@@ -182,7 +638,7 @@ impl Tree {
             open: true,
             id,
         };
-        tree.add(path, depth + 1, id + 1);
+        tree.add(file, depth + 1, id + 1);
         self.nodes.push(tree);
     }
 
@@ -202,6 +658,16 @@ impl Tree {
 mod tests {
     use super::*;
 
+    fn modified(path: PathBuf) -> File {
+        File {
+            path,
+            status: Delta::Modified,
+            old_path: None,
+            similarity: 0,
+            binary: None,
+        }
+    }
+
     #[test]
     fn test_tree() {
         let paths = vec![
@@ -210,7 +676,7 @@ mod tests {
             PathBuf::from(r"src/ui/file_area.rs"),
         ];
 
-        let tree = Tree::new(paths);
+        let tree = Tree::new(paths.into_iter().map(modified).collect());
 
         assert_eq!(tree.id, 0);
         assert_eq!(tree.name, "");
@@ -222,12 +688,8 @@ mod tests {
         assert_eq!(
             tree.nodes[0].files,
             vec![
-                File {
-                    path: PathBuf::from("src/data.rs")
-                },
-                File {
-                    path: PathBuf::from("src/test.rs")
-                }
+                modified(PathBuf::from("src/data.rs")),
+                modified(PathBuf::from("src/test.rs"))
             ]
         );
         assert_eq!(tree.nodes[0].nodes[0].id, 2);
@@ -235,9 +697,7 @@ mod tests {
         assert_eq!(tree.nodes[0].nodes[0].open, true);
         assert_eq!(
             tree.nodes[0].nodes[0].files,
-            vec![File {
-                path: PathBuf::from("src/ui/file_area.rs")
-            },]
+            vec![modified(PathBuf::from("src/ui/file_area.rs"))]
         );
     }
 }
@@ -0,0 +1,352 @@
+use std::{borrow::Cow, collections::HashMap, hash::Hash, ops::Range};
+
+use similar::{capture_diff, capture_diff_slices, group_diff_ops, Algorithm, DiffOp, DiffTag};
+
+use super::{
+    header::Header,
+    line::Line,
+    settings::{DiffAlgorithm, DiffSettings},
+};
+
+/// Lines occurring more often than this in a region are no anchors, git gives up on them too
+/// and leaves the region to Myers.
+const MAX_CHAIN: usize = 64;
+
+/// Diffs two sides split with `split` in-process, for the histogram algorithm and for files
+/// libgit2 can't split into lines.
+pub(super) fn diff_lines(
+    (old, old_newline): (&[String], bool),
+    (new, new_newline): (&[String], bool),
+    settings: &DiffSettings,
+) -> (Vec<Header>, Vec<Line>) {
+    let old_keys = keys(old, old_newline, settings);
+    let new_keys = keys(new, new_newline, settings);
+    let ops = match settings.algorithm {
+        DiffAlgorithm::Histogram => histogram(&old_keys, &new_keys),
+        DiffAlgorithm::Patience => capture_diff_slices(Algorithm::Patience, &old_keys, &new_keys),
+        DiffAlgorithm::Myers | DiffAlgorithm::Minimal => {
+            capture_diff_slices(Algorithm::Myers, &old_keys, &new_keys)
+        }
+    };
+
+    let old_last = (!old_newline).then(|| old.len().checked_sub(1)).flatten();
+    let new_last = (!new_newline).then(|| new.len().checked_sub(1)).flatten();
+    let mut headers = Vec::new();
+    let mut lines = Vec::new();
+    for group in group_diff_ops(ops, settings.context_lines as usize) {
+        let (Some(first), Some(last)) = (group.first(), group.last()) else {
+            continue;
+        };
+        let old_range = first.old_range().start..last.old_range().end;
+        let new_range = first.new_range().start..last.new_range().end;
+        // empty ranges start at the line before, like git does it
+        let start = |range: &Range<usize>| {
+            if range.is_empty() {
+                range.start
+            } else {
+                range.start + 1
+            }
+        };
+        let header = format!(
+            "@@ -{},{} +{},{} @@",
+            start(&old_range),
+            old_range.len(),
+            start(&new_range),
+            new_range.len()
+        );
+        if let Ok(header) = Header::new(header) {
+            headers.push(header);
+        }
+
+        for op in group {
+            let (tag, old_range, new_range) = op.as_tag_tuple();
+            if tag == DiffTag::Equal {
+                for (o, n) in old_range.zip(new_range) {
+                    lines.push(Line::new(
+                        Some(o as u32 + 1),
+                        Some(n as u32 + 1),
+                        new[n].clone(),
+                        ' ',
+                    ));
+                    if old_last == Some(o) {
+                        lines.push(no_newline('='));
+                    }
+                }
+                continue;
+            }
+
+            for o in old_range {
+                lines.push(Line::new(Some(o as u32 + 1), None, old[o].clone(), '-'));
+                if old_last == Some(o) {
+                    lines.push(no_newline('>'));
+                }
+            }
+            for n in new_range {
+                lines.push(Line::new(None, Some(n as u32 + 1), new[n].clone(), '+'));
+                if new_last == Some(n) {
+                    lines.push(no_newline('<'));
+                }
+            }
+        }
+    }
+
+    (headers, lines)
+}
+
+/// Lines of a text without their newline, a CR stays like in the lines from libgit2. The flag
+/// tells whether the text ends with a newline.
+pub(super) fn split(text: &str) -> (Vec<String>, bool) {
+    let newline = text.is_empty() || text.ends_with('\n');
+    let text = text.strip_suffix('\n').unwrap_or(text);
+    let lines = if text.is_empty() && newline {
+        Vec::new()
+    } else {
+        text.split('\n').map(str::to_owned).collect()
+    };
+
+    (lines, newline)
+}
+
+// a missing newline at the end changes the last line, lines never contain one themselves
+fn keys<'a>(lines: &'a [String], newline: bool, settings: &DiffSettings) -> Vec<Cow<'a, str>> {
+    lines
+        .iter()
+        .enumerate()
+        .map(|(i, line)| {
+            if !newline && i + 1 == lines.len() {
+                Cow::Owned(format!("{}\n", key(line, settings)))
+            } else {
+                key(line, settings)
+            }
+        })
+        .collect()
+}
+
+/// What a line is compared by, honoring the whitespace settings. Blank lines can't be ignored
+/// here, that only works with the algorithms of libgit2.
+fn key<'a>(line: &'a str, settings: &DiffSettings) -> Cow<'a, str> {
+    if settings.ignore_whitespace {
+        Cow::Owned(line.split_whitespace().collect())
+    } else if settings.ignore_whitespace_change {
+        Cow::Owned(line.split_whitespace().collect::<Vec<_>>().join(" "))
+    } else if settings.ignore_whitespace_eol {
+        Cow::Borrowed(line.trim_end())
+    } else {
+        Cow::Borrowed(line)
+    }
+}
+
+fn no_newline(origin: char) -> Line {
+    Line::new(
+        None,
+        None,
+        "\\ No newline at end of file".to_owned(),
+        origin,
+    )
+}
+
+/// Histogram diff like git's: the common run of lines that occur least often is matched up and
+/// the parts before and after it are diffed the same way.
+fn histogram<T: Eq + Hash + Ord>(old: &[T], new: &[T]) -> Vec<DiffOp> {
+    let mut ops = Vec::new();
+    histogram_range(old, 0..old.len(), new, 0..new.len(), &mut ops);
+    ops
+}
+
+// only the part before an anchor recurses, the part after it is taken by the loop like git does
+// it, so files where changes alternate with unique lines don't nest a call per line
+fn histogram_range<T: Eq + Hash + Ord>(
+    old: &[T],
+    mut o: Range<usize>,
+    new: &[T],
+    mut n: Range<usize>,
+    ops: &mut Vec<DiffOp>,
+) {
+    // ends of the ranges and length of the common suffixes, they go last and innermost first
+    let mut suffixes = Vec::new();
+    loop {
+        let prefix = o
+            .clone()
+            .zip(n.clone())
+            .take_while(|&(i, j)| old[i] == new[j])
+            .count();
+        push(
+            ops,
+            DiffTag::Equal,
+            o.start..o.start + prefix,
+            n.start..n.start + prefix,
+        );
+        o.start += prefix;
+        n.start += prefix;
+
+        let suffix = o
+            .clone()
+            .rev()
+            .zip(n.clone().rev())
+            .take_while(|&(i, j)| old[i] == new[j])
+            .count();
+        o.end -= suffix;
+        n.end -= suffix;
+        suffixes.push((o.end, n.end, suffix));
+
+        if o.is_empty() || n.is_empty() {
+            push(ops, DiffTag::Delete, o.clone(), n.start..n.start);
+            push(ops, DiffTag::Insert, o.end..o.end, n.clone());
+            break;
+        }
+        let Some((i, j, len)) = anchor(old, &o, new, &n) else {
+            for op in capture_diff(Algorithm::Myers, old, o.clone(), new, n.clone()) {
+                let (tag, old_range, new_range) = op.as_tag_tuple();
+                push(ops, tag, old_range, new_range);
+            }
+            break;
+        };
+
+        histogram_range(old, o.start..i, new, n.start..j, ops);
+        push(ops, DiffTag::Equal, i..i + len, j..j + len);
+        o.start = i + len;
+        n.start = j + len;
+    }
+
+    for (o_end, n_end, len) in suffixes.into_iter().rev() {
+        push(ops, DiffTag::Equal, o_end..o_end + len, n_end..n_end + len);
+    }
+}
+
+/// The common run whose rarest line occurs least often in the old range, the longer run wins a
+/// tie. Returns the start in both sides and the length.
+fn anchor<T: Eq + Hash>(
+    old: &[T],
+    o: &Range<usize>,
+    new: &[T],
+    n: &Range<usize>,
+) -> Option<(usize, usize, usize)> {
+    let mut occurrences: HashMap<&T, Vec<usize>> = HashMap::new();
+    for i in o.clone() {
+        occurrences.entry(&old[i]).or_default().push(i);
+    }
+
+    // start in old, start in new, length, occurrences of the rarest line
+    let mut best: Option<(usize, usize, usize, usize)> = None;
+    let mut j = n.start;
+    while j < n.end {
+        let mut next = j + 1;
+        let positions = occurrences
+            .get(&new[j])
+            .filter(|positions| positions.len() <= MAX_CHAIN);
+        for &i in positions.into_iter().flatten() {
+            let before = (o.start..i)
+                .rev()
+                .zip((n.start..j).rev())
+                .take_while(|&(i, j)| old[i] == new[j])
+                .count();
+            let after = (i..o.end)
+                .zip(j..n.end)
+                .take_while(|&(i, j)| old[i] == new[j])
+                .count();
+            let (start, len) = (i - before, before + after);
+            let rarest = (start..start + len)
+                .map(|k| occurrences.get(&old[k]).map_or(0, Vec::len))
+                .min()
+                .unwrap_or(usize::MAX);
+
+            let better = match best {
+                Some((_, _, best_len, best_rarest)) => {
+                    rarest < best_rarest || (rarest == best_rarest && len > best_len)
+                }
+                None => true,
+            };
+            if better {
+                best = Some((start, j - before, len, rarest));
+            }
+            next = next.max(j + after);
+        }
+        j = next;
+    }
+
+    best.map(|(i, j, len, _)| (i, j, len))
+}
+
+/// Adds an op, merging it into the last one when they continue each other.
+fn push(ops: &mut Vec<DiffOp>, tag: DiffTag, old: Range<usize>, new: Range<usize>) {
+    if old.is_empty() && new.is_empty() {
+        return;
+    }
+    if let Some(last) = ops.last_mut() {
+        let (last_tag, last_old, last_new) = last.as_tag_tuple();
+        if last_tag == tag && last_old.end == old.start && last_new.end == new.start {
+            *last = op(tag, last_old.start..old.end, last_new.start..new.end);
+            return;
+        }
+    }
+    ops.push(op(tag, old, new));
+}
+
+fn op(tag: DiffTag, old: Range<usize>, new: Range<usize>) -> DiffOp {
+    match tag {
+        DiffTag::Equal => DiffOp::Equal {
+            old_index: old.start,
+            new_index: new.start,
+            len: old.len(),
+        },
+        DiffTag::Delete => DiffOp::Delete {
+            old_index: old.start,
+            old_len: old.len(),
+            new_index: new.start,
+        },
+        DiffTag::Insert => DiffOp::Insert {
+            old_index: old.start,
+            new_index: new.start,
+            new_len: new.len(),
+        },
+        DiffTag::Replace => DiffOp::Replace {
+            old_index: old.start,
+            old_len: old.len(),
+            new_index: new.start,
+            new_len: new.len(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histogram_diff() {
+        let old = split("x\ny\nz");
+        let new = split("x\nY\nz\n");
+        let settings = DiffSettings {
+            algorithm: DiffAlgorithm::Histogram,
+            ..DiffSettings::default()
+        };
+
+        let (headers, lines) = diff_lines((&old.0, old.1), (&new.0, new.1), &settings);
+        assert_eq!(headers[0].content, "@@ -1,3 +1,3 @@");
+        let origins: String = lines.iter().map(|l| l.origin).collect();
+        assert_eq!(origins, " -->++");
+
+        // runs of rare lines are matched, the longer one wins
+        let old = ["{", "a", "}", "{", "b", "}"];
+        let new = ["{", "b", "}", "{", "a", "}"];
+        let ops = histogram(&old, &new);
+        let equal: Vec<_> = ops
+            .iter()
+            .filter(|op| op.tag() == DiffTag::Equal)
+            .map(|op| op.old_range())
+            .collect();
+        assert_eq!(equal, vec![0..1, 2..4, 5..6]);
+
+        // unique lines alternating with changes are one anchor each
+        let old: Vec<String> = (0..2_000).map(|i| format!("{i}")).collect();
+        let new: Vec<String> = (0..2_000)
+            .map(|i| match i % 2 {
+                0 => format!("{i}"),
+                _ => format!("changed {i}"),
+            })
+            .collect();
+        let ops = histogram(&old, &new);
+        let equal = ops.iter().filter(|op| op.tag() == DiffTag::Equal).count();
+        assert_eq!(equal, 1_000);
+    }
+}
@@ -0,0 +1,157 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use anyhow::{anyhow, Result};
+use chrono::NaiveDateTime;
+use git2::{Blame, BlameOptions, Repository};
+use similar::{capture_diff_slices, Algorithm, DiffOp};
+
+/// Which version of a file gets blamed, so the line numbers match the diff showing it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BlameTarget {
+    WorkDir,
+    Index,
+    /// Any revision git understands, like a commit id or branch name.
+    Revision(String),
+}
+
+/// The commit that last touched a line.
+#[derive(Debug, Clone)]
+pub struct BlameLine {
+    pub id: String,
+    pub author: String,
+    pub time: NaiveDateTime,
+    pub summary: String,
+}
+
+/// One entry per line of the file, `None` for lines that are not committed yet.
+pub fn get_blame(
+    path: &String,
+    file: &Path,
+    target: &BlameTarget,
+) -> Result<Vec<Option<BlameLine>>> {
+    let repo = Repository::open(path)?;
+
+    let buffer = match target {
+        BlameTarget::WorkDir => Some(fs::read(Path::new(path).join(file))?),
+        BlameTarget::Index => {
+            let index = repo.index()?;
+            let entry = index
+                .get_path(file, 0)
+                .ok_or_else(|| anyhow!("{} is not in the index", file.display()))?;
+            Some(repo.find_blob(entry.id)?.content().to_vec())
+        }
+        BlameTarget::Revision(_) => None,
+    };
+
+    let commit = match target {
+        BlameTarget::Revision(revision) => repo.revparse_single(revision)?.peel_to_commit()?,
+        _ => repo.head()?.peel_to_commit()?,
+    };
+    let Ok(entry) = commit.tree()?.get_path(file) else {
+        // a new file, nothing of it is committed yet
+        let lines = buffer.map_or(0, |b| String::from_utf8_lossy(&b).lines().count());
+        return Ok(vec![None; lines]);
+    };
+
+    let mut options = BlameOptions::new();
+    options.newest_commit(commit.id());
+    let blame = repo.blame_file(file, Some(&mut options))?;
+    let lines = blame_lines(&repo, &blame)?;
+
+    // git2 can't blame a buffer, so the uncommitted changes are diffed in afterwards
+    match buffer {
+        Some(buffer) => Ok(map_lines(
+            repo.find_blob(entry.id())?.content(),
+            &buffer,
+            &lines,
+        )),
+        None => Ok(lines),
+    }
+}
+
+fn blame_lines(repo: &Repository, blame: &Blame) -> Result<Vec<Option<BlameLine>>> {
+    let mut summaries = HashMap::new();
+    let mut lines = Vec::new();
+    for hunk in blame.iter() {
+        let id = hunk.final_commit_id();
+        let summary = match summaries.get(&id) {
+            Some(summary) => String::clone(summary),
+            None => {
+                let summary = repo.find_commit(id)?.summary().unwrap_or("").to_owned();
+                summaries.insert(id, summary.clone());
+                summary
+            }
+        };
+
+        let signature = hunk.final_signature();
+        let time = signature.when();
+        let line = BlameLine {
+            id: id.to_string(),
+            author: signature.name().unwrap_or("").to_owned(),
+            time: NaiveDateTime::from_timestamp_opt(
+                time.seconds() + time.offset_minutes() as i64 * 60,
+                0,
+            )
+            .unwrap_or_default(),
+            summary,
+        };
+
+        for _ in 0..hunk.lines_in_hunk() {
+            lines.push(Some(line.clone()));
+        }
+    }
+
+    Ok(lines)
+}
+
+/// Moves the blame of the committed version of a file over to the lines of a changed version,
+/// changed lines have no blame.
+fn map_lines(old: &[u8], new: &[u8], blame: &[Option<BlameLine>]) -> Vec<Option<BlameLine>> {
+    let old = String::from_utf8_lossy(old);
+    let new = String::from_utf8_lossy(new);
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+
+    let mut lines = vec![None; new_lines.len()];
+    for op in capture_diff_slices(Algorithm::Myers, &old_lines, &new_lines) {
+        if let DiffOp::Equal {
+            old_index,
+            new_index,
+            len,
+        } = op
+        {
+            for i in 0..len {
+                lines[new_index + i] = blame.get(old_index + i).cloned().flatten();
+            }
+        }
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(id: &str) -> Option<BlameLine> {
+        Some(BlameLine {
+            id: id.to_owned(),
+            author: String::new(),
+            time: NaiveDateTime::default(),
+            summary: String::new(),
+        })
+    }
+
+    #[test]
+    fn map_changed_lines() {
+        let blame = [line("a"), line("b"), line("c")];
+
+        let lines = map_lines(b"one\ntwo\nthree\n", b"zero\none\nthree\n", &blame);
+
+        let ids: Vec<Option<&str>> = lines
+            .iter()
+            .map(|l| l.as_ref().map(|l| l.id.as_str()))
+            .collect();
+        assert_eq!(ids, vec![None, Some("a"), Some("c")]);
+    }
+}
@@ -0,0 +1,155 @@
+use anyhow::{bail, Result};
+use git2::{build::CheckoutBuilder, BranchType, Oid, Repository};
+
+#[derive(Debug, Clone)]
+pub struct Branch {
+    /// Short name like `main` or `origin/main`.
+    pub name: String,
+    pub remote: bool,
+    /// Whether HEAD points at this branch.
+    pub head: bool,
+    pub upstream: Option<String>,
+    /// Commits the branch has that its upstream doesn't have.
+    pub ahead: usize,
+    pub behind: usize,
+}
+
+/// Local branches first, then remote-tracking branches, both sorted by name.
+pub fn get_branches(path: &String) -> Result<Vec<Branch>> {
+    let repo = Repository::open(path)?;
+
+    let mut branches = Vec::new();
+    for branch in repo.branches(None)? {
+        let (branch, branch_type) = branch?;
+        // symbolic refs like origin/HEAD only repeat their target
+        let (Some(name), Some(target)) = (branch.name()?, branch.get().target()) else {
+            continue;
+        };
+
+        let upstream = branch.upstream().ok();
+        let upstream_target = upstream.as_ref().and_then(|u| u.get().target());
+        let (ahead, behind) = match upstream_target {
+            Some(upstream_target) => repo.graph_ahead_behind(target, upstream_target)?,
+            None => (0, 0),
+        };
+
+        branches.push(Branch {
+            name: name.to_owned(),
+            remote: branch_type == BranchType::Remote,
+            head: branch.is_head(),
+            upstream: match &upstream {
+                Some(upstream) => upstream.name()?.map(str::to_owned),
+                None => None,
+            },
+            ahead,
+            behind,
+        });
+    }
+
+    branches.sort_by(|a, b| (a.remote, &a.name).cmp(&(b.remote, &b.name)));
+    Ok(branches)
+}
+
+/// The name of the checked out branch, or the short id of HEAD when it is detached.
+pub fn current(path: &String) -> Result<String> {
+    let repo = Repository::open(path)?;
+    let head = match repo.head() {
+        Ok(head) => head,
+        // a new repository has a branch without commits
+        Err(e) if e.code() == git2::ErrorCode::UnbornBranch => {
+            let head = repo.find_reference("HEAD")?;
+            let target = head.symbolic_target().unwrap_or("");
+            return Ok(target.trim_start_matches("refs/heads/").to_owned());
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    if head.is_branch() {
+        Ok(head.shorthand().unwrap_or("").to_owned())
+    } else {
+        let id = head.peel_to_commit()?.id().to_string();
+        Ok(format!("detached at {}", &id[..7]))
+    }
+}
+
+/// Checks out a local branch, a remote-tracking branch gets a local branch tracking it first.
+/// Fails instead of overwriting local changes.
+pub fn checkout(path: &String, name: &str, remote: bool) -> Result<()> {
+    let repo = Repository::open(path)?;
+
+    let branch = if remote {
+        let remote_branch = repo.find_branch(name, BranchType::Remote)?;
+        let local_name = name.split_once('/').map_or(name, |(_, n)| n);
+        match repo.find_branch(local_name, BranchType::Local) {
+            Ok(local) => local,
+            Err(_) => {
+                let commit = remote_branch.get().peel_to_commit()?;
+                let mut local = repo.branch(local_name, &commit, false)?;
+                local.set_upstream(Some(name))?;
+                local
+            }
+        }
+    } else {
+        repo.find_branch(name, BranchType::Local)?
+    };
+
+    let reference = branch.into_reference();
+    let Some(reference_name) = reference.name() else {
+        bail!("Invalid branch name");
+    };
+    let tree = reference.peel_to_tree()?;
+    repo.checkout_tree(tree.as_object(), Some(CheckoutBuilder::new().safe()))?;
+    repo.set_head(reference_name)?;
+    Ok(())
+}
+
+pub fn create(path: &String, name: &str, start: Option<&str>) -> Result<()> {
+    let repo = Repository::open(path)?;
+    let commit = match start {
+        Some(id) => repo.find_commit(Oid::from_str(id)?)?,
+        None => repo.head()?.peel_to_commit()?,
+    };
+    repo.branch(name, &commit, false)?;
+    Ok(())
+}
+
+pub fn rename(path: &String, name: &str, new_name: &str) -> Result<()> {
+    let repo = Repository::open(path)?;
+    let mut branch = repo.find_branch(name, BranchType::Local)?;
+    branch.rename(new_name, false)?;
+    Ok(())
+}
+
+/// Deletes a local branch, unless it has commits that neither HEAD nor its upstream contain
+/// and `force` isn't set.
+pub fn delete(path: &String, name: &str, force: bool) -> Result<()> {
+    let repo = Repository::open(path)?;
+    let mut branch = repo.find_branch(name, BranchType::Local)?;
+
+    if !force && !is_merged(&repo, &branch)? {
+        bail!("Branch {name} is not fully merged");
+    }
+
+    branch.delete()?;
+    Ok(())
+}
+
+/// Whether everything on the local branch is contained in HEAD or its upstream, so deleting it
+/// loses no commits.
+pub fn merged(path: &String, name: &str) -> Result<bool> {
+    let repo = Repository::open(path)?;
+    let branch = repo.find_branch(name, BranchType::Local)?;
+    is_merged(&repo, &branch)
+}
+
+fn is_merged(repo: &Repository, branch: &git2::Branch) -> Result<bool> {
+    let Some(target) = branch.get().target() else {
+        bail!("Branch has no target");
+    };
+    let upstream = branch.upstream().ok().and_then(|u| u.get().target());
+    let head = repo.head().ok().and_then(|h| h.target());
+    Ok([head, upstream]
+        .into_iter()
+        .flatten()
+        .any(|base| base == target || repo.graph_descendant_of(base, target).unwrap_or(false)))
+}
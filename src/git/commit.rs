@@ -1,6 +1,15 @@
-use anyhow::Result;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Result};
 use chrono::NaiveDateTime;
-use git2::{Repository, Sort};
+use git2::{
+    Delta, DiffFindOptions, ErrorCode, Oid, Repository, RepositoryState, Revwalk, Sort, Tree,
+};
+
+use super::hooks;
 
 #[derive(Debug, Clone)]
 pub struct Commit {
@@ -8,53 +17,344 @@ pub struct Commit {
     pub author: Author,
     pub message: String,
     pub time: NaiveDateTime,
+    pub parents: Vec<String>,
+    /// Branches, tags and HEAD pointing at this commit.
+    pub refs: Vec<Reference>,
 }
 
 impl Commit {
-    pub fn contains(&self, search_string: &str) -> bool {
-        let search_string = &search_string.to_lowercase();
-        self.author.name.to_lowercase().contains(search_string)
-            || self.author.email.to_lowercase().contains(search_string)
-            || self.message.to_lowercase().contains(search_string)
+    pub(super) fn new(commit: &git2::Commit, refs: Vec<Reference>) -> Commit {
+        let author = Author {
+            name: commit.author().name().unwrap_or("").to_owned(),
+            email: commit.author().email().unwrap_or("").to_owned(),
+        };
+
+        Commit {
+            id: commit.id().to_string(),
+            author,
+            message: commit.message().unwrap_or("").to_owned(),
+            time: NaiveDateTime::from_timestamp_opt(
+                commit.time().seconds() + commit.time().offset_minutes() as i64 * 60,
+                0,
+            )
+            .unwrap_or_default(),
+            parents: commit.parent_ids().map(|id| id.to_string()).collect(),
+            refs,
+        }
     }
 }
 
+/// A commit that changed a file, together with the path the file had in it.
+#[derive(Debug, Clone)]
+pub struct FileCommit {
+    pub commit: Commit,
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reference {
+    pub name: String,
+    pub kind: ReferenceKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReferenceKind {
+    Head,
+    Branch,
+    Remote,
+    Tag,
+}
+
 #[derive(Debug, Clone)]
 pub struct Author {
     pub name: String,
     pub email: String,
 }
 
-pub fn get_log(path: &String) -> Result<Vec<Commit>> {
+/// Walks the history from HEAD in topological order, `all_branches` adds every local branch and
+/// remote-tracking ref as a starting point. The commits are handed out in pages of `page_size`
+/// together with whether the walk is complete, the walk stops when `on_page` returns false.
+pub fn walk_log(
+    path: &String,
+    all_branches: bool,
+    page_size: usize,
+    mut on_page: impl FnMut(Vec<Commit>, bool) -> bool,
+) -> Result<()> {
     let repo = Repository::open(path)?;
+    let mut revwalk = revwalk(&repo, all_branches)?.peekable();
+    let mut references = references(&repo)?;
+
+    loop {
+        let mut commits = Vec::with_capacity(page_size);
+        for id in revwalk.by_ref().take(page_size) {
+            let id = id?;
+            let commit = repo.find_commit(id)?;
+            let refs = references.remove(&id).unwrap_or_default();
+            commits.push(Commit::new(&commit, refs));
+        }
+
+        let complete = revwalk.peek().is_none();
+        if !on_page(commits, complete) || complete {
+            return Ok(());
+        }
+    }
+}
+
+pub(super) fn revwalk(repo: &Repository, all_branches: bool) -> Result<Revwalk<'_>> {
     let mut revwalk = repo.revwalk()?;
-    revwalk.set_sorting(Sort::TIME)?;
+    // the graph and the paths of the file log rely on children coming before their parents,
+    // which sorting by time alone doesn't guarantee with skewed clocks
+    revwalk.set_sorting(Sort::TOPOLOGICAL | Sort::TIME)?;
     revwalk.push_head()?;
+    if all_branches {
+        revwalk.push_glob("refs/heads")?;
+        revwalk.push_glob("refs/remotes")?;
+    }
+
+    Ok(revwalk)
+}
 
+/// Walks the history from HEAD and keeps the commits that changed `file`, merges only when they
+/// differ from all of their parents. With `follow` the path is tracked per commit, parents
+/// before a rename continue with the old path. Pages are handed out like in `walk_log`, and
+/// `running` is asked before every commit, as commits touching the file can be far apart.
+pub fn walk_file_log(
+    path: &String,
+    file: &Path,
+    follow: bool,
+    page_size: usize,
+    running: impl Fn() -> bool,
+    mut on_page: impl FnMut(Vec<FileCommit>, bool) -> bool,
+) -> Result<()> {
+    let repo = Repository::open(path)?;
+    let revwalk = revwalk(&repo, false)?;
+
+    let mut references = references(&repo)?;
+    // the path of the file in commits not walked yet, as their children know it
+    let mut paths: HashMap<Oid, PathBuf> = HashMap::new();
     let mut commits = Vec::new();
 
     for id in revwalk {
-        let id = id?;
-        let commit = repo.find_commit(id)?;
+        if !running() {
+            return Ok(());
+        }
 
-        let author = Author {
-            name: commit.author().name().unwrap_or("").to_owned(),
-            email: commit.author().email().unwrap_or("").to_owned(),
+        let commit = repo.find_commit(id?)?;
+        let file = paths
+            .remove(&commit.id())
+            .unwrap_or_else(|| file.to_path_buf());
+        let tree = commit.tree()?;
+        let entry = entry_id(&tree, &file);
+        let parents = commit
+            .parents()
+            .map(|p| p.tree())
+            .collect::<Result<Vec<Tree>, git2::Error>>()?;
+
+        let changed = if parents.is_empty() {
+            entry.is_some()
+        } else {
+            parents.iter().all(|p| entry_id(p, &file) != entry)
         };
 
-        let commit = Commit {
-            id: id.to_string(),
-            author,
-            message: commit.message().unwrap_or("").to_owned(),
-            time: NaiveDateTime::from_timestamp_opt(
-                commit.time().seconds() + commit.time().offset_minutes() as i64 * 60,
-                0,
-            )
-            .unwrap_or_default(),
+        for (parent, parent_tree) in commit.parent_ids().zip(&parents) {
+            let renamed = if follow && entry.is_some() && entry_id(parent_tree, &file).is_none() {
+                rename_source(&repo, parent_tree, &tree, &file)?
+            } else {
+                None
+            };
+            // the first child to reach a parent decides its path
+            paths
+                .entry(parent)
+                .or_insert_with(|| renamed.unwrap_or_else(|| file.clone()));
+        }
+
+        if !changed {
+            continue;
+        }
+
+        let refs = references.remove(&commit.id()).unwrap_or_default();
+        commits.push(FileCommit {
+            commit: Commit::new(&commit, refs),
+            path: file,
+        });
+
+        if commits.len() == page_size && !on_page(std::mem::take(&mut commits), false) {
+            return Ok(());
+        }
+    }
+
+    on_page(commits, true);
+    Ok(())
+}
+
+fn entry_id(tree: &Tree, file: &Path) -> Option<Oid> {
+    tree.get_path(file).ok().map(|entry| entry.id())
+}
+
+/// The path `file` had in `old_tree` if it was renamed on the way to `new_tree`.
+fn rename_source(
+    repo: &Repository,
+    old_tree: &Tree,
+    new_tree: &Tree,
+    file: &Path,
+) -> Result<Option<PathBuf>> {
+    let mut diff = repo.diff_tree_to_tree(Some(old_tree), Some(new_tree), None)?;
+    diff.find_similar(Some(DiffFindOptions::new().renames(true)))?;
+
+    let old_path = diff
+        .deltas()
+        .find(|d| d.status() == Delta::Renamed && d.new_file().path() == Some(file))
+        .and_then(|d| d.old_file().path().map(Path::to_path_buf));
+    Ok(old_path)
+}
+
+/// Identifies the state of HEAD and all references, the log only needs to be reloaded when it
+/// changes.
+pub fn log_key(path: &String, all_branches: bool) -> Result<String> {
+    let repo = Repository::open(path)?;
+    let mut key = format!("{} {}", all_branches, repo.head()?.peel_to_commit()?.id());
+
+    for reference in repo.references()? {
+        let reference = reference?;
+        if let (Some(name), Some(target)) = (reference.name(), reference.target()) {
+            key.push_str(&format!("\n{name} {target}"));
+        }
+    }
+
+    Ok(key)
+}
+
+pub fn get_commit(path: &String, id: &str) -> Result<Commit> {
+    let repo = Repository::open(path)?;
+    let commit = repo.find_commit(Oid::from_str(id)?)?;
+    let refs = references(&repo)?.remove(&commit.id()).unwrap_or_default();
+    Ok(Commit::new(&commit, refs))
+}
+
+/// The identity new commits are created with, from the git config.
+pub fn author(path: &String) -> Result<Author> {
+    let repo = Repository::open(path)?;
+    let signature = repo.signature()?;
+    Ok(Author {
+        name: signature.name().unwrap_or("").to_owned(),
+        email: signature.email().unwrap_or("").to_owned(),
+    })
+}
+
+/// The message of the commit HEAD points at, to start amending from.
+pub fn head_message(path: &String) -> Result<String> {
+    let repo = Repository::open(path)?;
+    let commit = repo.head()?.peel_to_commit()?;
+    Ok(commit.message().unwrap_or("").to_owned())
+}
+
+/// Commits the index, or replaces the HEAD commit with it when amending. An ongoing merge is
+/// concluded with the merged commits as additional parents.
+pub fn create(path: &String, message: &str, amend: bool, run_hooks: bool) -> Result<()> {
+    let mut repo = Repository::open(path)?;
+    let signature = repo.signature()?;
+
+    let merging = !amend && repo.state() == RepositoryState::Merge;
+    let mut merge_heads = Vec::new();
+    if merging {
+        repo.mergehead_foreach(|id| {
+            merge_heads.push(*id);
+            true
+        })?;
+    }
+
+    let mut message = message.to_owned();
+    if run_hooks {
+        hooks::run(&repo, "pre-commit", &[])?;
+        message = hooks::commit_msg(&repo, &message)?;
+    }
+    let message = git2::message_prettify(message, Some(b'#'))?;
+    if message.is_empty() {
+        bail!("Aborting commit due to empty commit message");
+    }
+
+    // the pre-commit hook may have staged changes
+    let tree = repo.find_tree(repo.index()?.write_tree()?)?;
+
+    if amend {
+        let head = repo.head()?.peel_to_commit()?;
+        head.amend(
+            Some("HEAD"),
+            None,
+            Some(&signature),
+            None,
+            Some(&message),
+            Some(&tree),
+        )?;
+        return Ok(());
+    }
+
+    let mut parents = Vec::new();
+    match repo.head() {
+        Ok(head) => parents.push(head.peel_to_commit()?),
+        Err(e) if e.code() == ErrorCode::UnbornBranch => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    for id in merge_heads {
+        parents.push(repo.find_commit(id)?);
+    }
+
+    let parents: Vec<&git2::Commit> = parents.iter().collect();
+    repo.commit(
+        Some("HEAD"),
+        &signature,
+        &signature,
+        &message,
+        &tree,
+        &parents,
+    )?;
+
+    if merging {
+        repo.cleanup_state()?;
+    }
+
+    Ok(())
+}
+
+/// All references of the repository grouped by the commit they point at.
+pub(super) fn references(repo: &Repository) -> Result<HashMap<Oid, Vec<Reference>>> {
+    let mut references: HashMap<Oid, Vec<Reference>> = HashMap::new();
+
+    if let Ok(head) = repo.head() {
+        if let Ok(commit) = head.peel_to_commit() {
+            references.entry(commit.id()).or_default().push(Reference {
+                name: "HEAD".to_owned(),
+                kind: ReferenceKind::Head,
+            });
+        }
+    }
+
+    for reference in repo.references()? {
+        let reference = reference?;
+        let kind = if reference.is_branch() {
+            ReferenceKind::Branch
+        } else if reference.is_remote() {
+            ReferenceKind::Remote
+        } else if reference.is_tag() {
+            ReferenceKind::Tag
+        } else {
+            continue;
         };
 
-        commits.push(commit);
+        // symbolic refs like origin/HEAD only repeat their target
+        if reference.symbolic_target().is_some() {
+            continue;
+        }
+
+        let (Some(name), Ok(commit)) = (reference.shorthand(), reference.peel_to_commit()) else {
+            continue;
+        };
+        references.entry(commit.id()).or_default().push(Reference {
+            name: name.to_owned(),
+            kind,
+        });
     }
 
-    Ok(commits)
+    Ok(references)
 }
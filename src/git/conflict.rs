@@ -0,0 +1,238 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+use git2::{IndexEntry, Repository};
+
+/// A file the index has conflict entries for.
+#[derive(Debug, Clone)]
+pub struct ConflictFile {
+    pub path: PathBuf,
+    /// Content of the common ancestor, ours and theirs from the index stages 1 to 3, empty if the
+    /// file doesn't exist on that side.
+    pub base: String,
+    pub ours: String,
+    pub theirs: String,
+    /// The conflict markers left in the working tree.
+    pub regions: Vec<Region>,
+}
+
+/// One `<<<<<<<` to `>>>>>>>` block, lines keep their line endings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Region {
+    pub ours: Vec<String>,
+    /// Only present with the diff3 conflict style.
+    pub base: Vec<String>,
+    pub theirs: Vec<String>,
+    raw: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Resolution {
+    Ours,
+    Theirs,
+    Both,
+}
+
+enum Segment {
+    Text(String),
+    Conflict(Region),
+}
+
+pub fn get_conflicts(path: &String) -> Result<Vec<ConflictFile>> {
+    let repo = Repository::open(path)?;
+    let index = repo.index()?;
+    if !index.has_conflicts() {
+        return Ok(Vec::new());
+    }
+
+    let content = |entry: &Option<IndexEntry>| -> Result<String> {
+        match entry {
+            Some(entry) => {
+                Ok(String::from_utf8_lossy(repo.find_blob(entry.id)?.content()).into_owned())
+            }
+            None => Ok(String::new()),
+        }
+    };
+
+    let mut conflicts = Vec::new();
+    for conflict in index.conflicts()? {
+        let conflict = conflict?;
+        let entry = [&conflict.our, &conflict.their, &conflict.ancestor]
+            .into_iter()
+            .flatten()
+            .next()
+            .ok_or_else(|| anyhow!("Conflict without entries"))?;
+        let file = PathBuf::from(String::from_utf8_lossy(&entry.path).into_owned());
+
+        let working_tree = fs::read(Path::new(path).join(&file)).unwrap_or_default();
+        let regions = parse(&String::from_utf8_lossy(&working_tree))
+            .into_iter()
+            .filter_map(|s| match s {
+                Segment::Conflict(region) => Some(region),
+                Segment::Text(_) => None,
+            })
+            .collect();
+
+        conflicts.push(ConflictFile {
+            base: content(&conflict.ancestor)?,
+            ours: content(&conflict.our)?,
+            theirs: content(&conflict.their)?,
+            path: file,
+            regions,
+        });
+    }
+
+    Ok(conflicts)
+}
+
+/// Replaces the conflict region with the given index in the working tree file.
+pub fn resolve(path: &String, file: &Path, region: usize, resolution: Resolution) -> Result<()> {
+    let file = Path::new(path).join(file);
+    let content = String::from_utf8(fs::read(&file)?)?;
+    let resolved = resolve_region(&content, region, resolution)
+        .ok_or_else(|| anyhow!("Conflict {} not found", region + 1))?;
+    fs::write(file, resolved)?;
+    Ok(())
+}
+
+fn resolve_region(content: &str, index: usize, resolution: Resolution) -> Option<String> {
+    let mut result = String::new();
+    let mut found = false;
+    let mut region_index = 0;
+
+    for segment in parse(content) {
+        match segment {
+            Segment::Text(text) => result.push_str(&text),
+            Segment::Conflict(region) if region_index == index => {
+                let lines = match resolution {
+                    Resolution::Ours => region.ours,
+                    Resolution::Theirs => region.theirs,
+                    Resolution::Both => [region.ours, region.theirs].concat(),
+                };
+                result.extend(lines);
+                found = true;
+                region_index += 1;
+            }
+            Segment::Conflict(region) => {
+                result.push_str(&region.raw);
+                region_index += 1;
+            }
+        }
+    }
+
+    found.then_some(result)
+}
+
+fn parse(content: &str) -> Vec<Segment> {
+    enum State {
+        Outside,
+        Ours,
+        Base,
+        Theirs,
+    }
+
+    let mut segments = Vec::new();
+    let mut state = State::Outside;
+    let mut region = Region {
+        ours: Vec::new(),
+        base: Vec::new(),
+        theirs: Vec::new(),
+        raw: String::new(),
+    };
+
+    for line in content.split_inclusive('\n') {
+        if !matches!(state, State::Outside) {
+            region.raw.push_str(line);
+        }
+
+        state = match state {
+            State::Outside if is_marker(line, '<') => {
+                region.raw.push_str(line);
+                State::Ours
+            }
+            State::Outside => {
+                segments.push(Segment::Text(line.to_owned()));
+                State::Outside
+            }
+            State::Ours | State::Base if is_marker(line, '=') => State::Theirs,
+            State::Ours if is_marker(line, '|') => State::Base,
+            State::Ours => {
+                region.ours.push(line.to_owned());
+                State::Ours
+            }
+            State::Base => {
+                region.base.push(line.to_owned());
+                State::Base
+            }
+            State::Theirs if is_marker(line, '>') => {
+                let raw = std::mem::take(&mut region.raw);
+                segments.push(Segment::Conflict(Region {
+                    ours: std::mem::take(&mut region.ours),
+                    base: std::mem::take(&mut region.base),
+                    theirs: std::mem::take(&mut region.theirs),
+                    raw,
+                }));
+                State::Outside
+            }
+            State::Theirs => {
+                region.theirs.push(line.to_owned());
+                State::Theirs
+            }
+        };
+    }
+
+    // markers that are never closed are just text
+    if !region.raw.is_empty() {
+        segments.push(Segment::Text(region.raw));
+    }
+
+    segments
+}
+
+/// Markers are exactly seven characters, followed by whitespace or the end of the line like git
+/// writes them, so lines like `========` in the content don't count.
+fn is_marker(line: &str, c: char) -> bool {
+    let mut chars = line.chars();
+    if chars.by_ref().take(7).filter(|&d| d == c).count() < 7 {
+        return false;
+    }
+
+    match chars.next() {
+        Some(next) => next.is_whitespace(),
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTENT: &str = "a\n<<<<<<< HEAD\nours\n||||||| base\nbase\n=======\ntheirs\n>>>>>>> other\nb\n<<<<<<< HEAD\n1\n=======\n2\n>>>>>>> other\n";
+
+    #[test]
+    fn resolve_regions() {
+        assert_eq!(
+            resolve_region(CONTENT, 0, Resolution::Ours).unwrap(),
+            "a\nours\nb\n<<<<<<< HEAD\n1\n=======\n2\n>>>>>>> other\n"
+        );
+        assert_eq!(
+            resolve_region(CONTENT, 1, Resolution::Both).unwrap(),
+            "a\n<<<<<<< HEAD\nours\n||||||| base\nbase\n=======\ntheirs\n>>>>>>> other\nb\n1\n2\n"
+        );
+        assert_eq!(resolve_region(CONTENT, 2, Resolution::Theirs), None);
+    }
+
+    #[test]
+    fn markers_are_seven_characters() {
+        let content = "<<<<<<< HEAD\n========\n=======\n>>>>>>>>\n>>>>>>>\n";
+        assert_eq!(
+            resolve_region(content, 0, Resolution::Ours).unwrap(),
+            "========\n"
+        );
+        assert!(is_marker("=======\r\n", '='));
+        assert!(!is_marker("=======x", '='));
+    }
+}
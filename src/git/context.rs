@@ -0,0 +1,152 @@
+use std::ops::Range;
+
+use super::{header::Header, hunk_ranges, line::Line};
+
+/// Adds the lines of the new side listed in `expanded` as context where they border a hunk,
+/// hunks that meet are merged. `content` holds the lines of the new side.
+pub(super) fn expand(
+    headers: &[Header],
+    lines: &[Line],
+    content: &[String],
+    expanded: &[Range<u32>],
+) -> (Vec<Header>, Vec<Line>) {
+    let shown =
+        |n: u32| n >= 1 && n as usize <= content.len() && expanded.iter().any(|r| r.contains(&n));
+    let context = |n: u32, shift: i64| {
+        Line::new(
+            Some((n as i64 - shift) as u32),
+            Some(n),
+            content[n as usize - 1].clone(),
+            ' ',
+        )
+    };
+
+    let starts: Vec<u32> = headers.iter().map(new_start).collect();
+    let mut hunks: Vec<Hunk> = Vec::new();
+    // first line of the new side that no hunk covers yet
+    let mut covered = 1;
+
+    for (i, (header, range)) in headers.iter().zip(hunk_ranges(headers, lines)).enumerate() {
+        let start = starts[i];
+        let shift = start as i64 - old_start(header) as i64;
+
+        let mut above = 0;
+        while start - above > covered && shown(start - above - 1) {
+            above += 1;
+        }
+
+        let mut hunk = Hunk {
+            old_start: old_start(header) - above,
+            new_start: start - above,
+            old_lines: header.old_lines + above,
+            new_lines: header.lines + above,
+            suffix: header
+                .content
+                .splitn(3, "@@")
+                .nth(2)
+                .unwrap_or("")
+                .to_owned(),
+            lines: (start - above..start).map(|n| context(n, shift)).collect(),
+        };
+        hunk.lines.extend_from_slice(&lines[range]);
+
+        let end = start + header.lines;
+        let shift = end as i64 - (old_start(header) + header.old_lines) as i64;
+        let next = starts.get(i + 1).copied().unwrap_or(u32::MAX);
+        let mut below = 0;
+        while end + below < next && shown(end + below) {
+            hunk.lines.push(context(end + below, shift));
+            below += 1;
+        }
+        hunk.old_lines += below;
+        hunk.new_lines += below;
+
+        match hunks.last_mut() {
+            Some(previous) if previous.new_start + previous.new_lines == hunk.new_start => {
+                previous.old_lines += hunk.old_lines;
+                previous.new_lines += hunk.new_lines;
+                previous.lines.extend(hunk.lines);
+            }
+            _ => hunks.push(hunk),
+        }
+        covered = end + below;
+    }
+
+    let mut result_headers = Vec::new();
+    let mut result_lines = Vec::new();
+    for hunk in hunks {
+        // empty sides start at the line before, like git does it
+        let header = format!(
+            "@@ -{},{} +{},{} @@{}",
+            hunk.old_start - (hunk.old_lines == 0) as u32,
+            hunk.old_lines,
+            hunk.new_start - (hunk.new_lines == 0) as u32,
+            hunk.new_lines,
+            hunk.suffix
+        );
+        if let Ok(header) = Header::new(header) {
+            result_headers.push(header);
+            result_lines.extend(hunk.lines);
+        }
+    }
+
+    (result_headers, result_lines)
+}
+
+/// First line of the new side in the hunk.
+pub fn new_start(header: &Header) -> u32 {
+    match header.lines {
+        0 => header.line + 1,
+        _ => header.line,
+    }
+}
+
+fn old_start(header: &Header) -> u32 {
+    match header.old_lines {
+        0 => header.old_line + 1,
+        _ => header.old_line,
+    }
+}
+
+struct Hunk {
+    old_start: u32,
+    new_start: u32,
+    old_lines: u32,
+    new_lines: u32,
+    suffix: String,
+    lines: Vec<Line>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expand_and_merge() {
+        let content: Vec<String> = (1..=10).map(|n| n.to_string()).collect();
+        let headers = vec![
+            Header::new("@@ -2,1 +2,1 @@ fn a".to_string()).unwrap(),
+            Header::new("@@ -8,0 +9,1 @@".to_string()).unwrap(),
+        ];
+        let lines = vec![
+            Line::new(Some(2), None, "two".to_string(), '-'),
+            Line::new(None, Some(2), "2".to_string(), '+'),
+            Line::new(None, Some(9), "9".to_string(), '+'),
+        ];
+
+        let (result_headers, result_lines) =
+            expand(&headers, &lines, &content, &[1..2, 3..5, 8..9]);
+        let contents: Vec<_> = result_headers.iter().map(|h| h.content.as_str()).collect();
+        assert_eq!(contents, vec!["@@ -1,4 +1,4 @@ fn a", "@@ -8,1 +8,2 @@"]);
+        assert_eq!(result_lines.len(), 7);
+        assert_eq!(result_lines[4].old_lineno, Some(4));
+        assert_eq!(result_lines[5].old_lineno, Some(8));
+
+        // the whole gap between the hunks merges them
+        let (result_headers, result_lines) =
+            expand(&headers, &lines, &content, &[Range { start: 3, end: 9 }]);
+        let contents: Vec<_> = result_headers.iter().map(|h| h.content.as_str()).collect();
+        assert_eq!(contents, vec!["@@ -2,7 +2,8 @@ fn a"]);
+        assert_eq!(result_lines.len(), 9);
+    }
+}
@@ -0,0 +1,155 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+use chrono::{Local, NaiveDateTime};
+use git2::{
+    build::CheckoutBuilder, ApplyLocation, Delta, DiffOptions, Index, IndexEntry, IndexTime, Patch,
+    Repository,
+};
+
+use super::{stage, Diff};
+
+/// Changes removed from the working tree, kept around so they can be restored.
+#[derive(Debug, Clone)]
+pub struct Discarded {
+    pub file: PathBuf,
+    /// Patch that brings the discarded changes back, in the encoding of the file.
+    pub patch: Vec<u8>,
+    pub time: NaiveDateTime,
+}
+
+/// Reverts the whole file in the working tree to its state in the index, untracked files are
+/// deleted.
+pub fn discard_file(path: &String, file: &Path) -> Result<Discarded> {
+    if Path::new(path).join(file).is_dir() {
+        bail!("untracked directories can only be discarded file by file");
+    }
+
+    let repo = Repository::open(path)?;
+
+    // the changes between index and working tree are exactly what is needed for restoring,
+    // binary files need their full content in the patch to be restorable
+    let mut options = DiffOptions::new();
+    options
+        .show_binary(true)
+        .pathspec(file)
+        .disable_pathspec_match(true)
+        .include_untracked(true)
+        .show_untracked_content(true);
+    let diff = repo.diff_index_to_workdir(None, Some(&mut options))?;
+    let mut patch = Patch::from_diff(&diff, 0)?.context("file has no changes")?;
+    let patch = patch.to_buf()?.to_vec();
+
+    if diff.deltas().any(|d| d.status() == Delta::Untracked) {
+        fs::remove_file(Path::new(path).join(file))?;
+    } else {
+        let mut index = repo.index()?;
+        repo.checkout_index(
+            Some(&mut index),
+            Some(CheckoutBuilder::new().force().path(file)),
+        )?;
+    }
+
+    Ok(Discarded {
+        file: file.to_path_buf(),
+        patch,
+        time: Local::now().naive_local(),
+    })
+}
+
+/// Reverts the given lines of a diff between index and working tree.
+pub fn discard_lines(path: &String, diff: &Diff, selected: &[usize]) -> Result<Discarded> {
+    let file = diff.file_name();
+    let patch = stage::patch(diff, selected, true).context("no changes selected")?;
+    let patch = stage::encode(diff, &patch)?;
+    let before = read(path, &file);
+
+    // the undo record has to exist before the working tree changes
+    let repo = Repository::open(path)?;
+    let diff = git2::Diff::from_buffer(&patch)?;
+    let after = applied(&repo, &file, &before, &diff)?;
+    let discarded = discarded(&file, &after, &before)?;
+    repo.apply(&diff, ApplyLocation::WorkDir, None)?;
+
+    Ok(discarded)
+}
+
+pub fn restore(path: &String, discarded: &Discarded) -> Result<()> {
+    let repo = Repository::open(path)?;
+    let diff = git2::Diff::from_buffer(&discarded.patch)?;
+    repo.apply(&diff, ApplyLocation::WorkDir, None)?;
+    Ok(())
+}
+
+/// Writes the patch to `.git/contrast/discarded` so it survives restarts.
+pub fn save(path: &String, discarded: &Discarded) -> Result<PathBuf> {
+    let repo = Repository::open(path)?;
+    let dir = repo.path().join("contrast").join("discarded");
+    fs::create_dir_all(&dir)?;
+
+    let name = discarded
+        .file
+        .to_str()
+        .context("invalid file name")?
+        .replace(['/', '\\'], "_");
+    let file = dir.join(format!(
+        "{}-{}.patch",
+        discarded.time.format("%Y%m%d-%H%M%S"),
+        name
+    ));
+    fs::write(&file, &discarded.patch)?;
+
+    Ok(file)
+}
+
+/// Content of the file once the patch is applied, worked out on a tree holding only the file.
+fn applied(repo: &Repository, file: &Path, before: &[u8], patch: &git2::Diff) -> Result<Vec<u8>> {
+    let entry = IndexEntry {
+        ctime: IndexTime::new(0, 0),
+        mtime: IndexTime::new(0, 0),
+        dev: 0,
+        ino: 0,
+        mode: 0o100644,
+        uid: 0,
+        gid: 0,
+        file_size: before.len() as u32,
+        id: repo.blob(before)?,
+        flags: 0,
+        flags_extended: 0,
+        path: file
+            .to_str()
+            .context("invalid file name")?
+            .as_bytes()
+            .to_vec(),
+    };
+    let mut index = Index::new()?;
+    index.add(&entry)?;
+    let tree = repo.find_tree(index.write_tree_to(repo)?)?;
+
+    let index = repo.apply_to_tree(&tree, patch, None)?;
+    match index.get_path(file, 0) {
+        Some(entry) => Ok(repo.find_blob(entry.id)?.content().to_vec()),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn discarded(file: &Path, after: &[u8], before: &[u8]) -> Result<Discarded> {
+    let mut options = DiffOptions::new();
+    options.show_binary(true);
+    let mut patch = Patch::from_buffers(after, Some(file), before, Some(file), Some(&mut options))?;
+    let patch = patch.to_buf()?.to_vec();
+
+    Ok(Discarded {
+        file: file.to_path_buf(),
+        patch,
+        time: Local::now().naive_local(),
+    })
+}
+
+// a missing file is treated as empty
+fn read(path: &String, file: &Path) -> Vec<u8> {
+    fs::read(Path::new(path).join(file)).unwrap_or_default()
+}
@@ -0,0 +1,173 @@
+use std::path::Path;
+
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+use git2::{AttrCheckFlags, DiffDelta, Repository};
+
+use super::{algorithm, header::Header, line::Line, settings::DiffSettings};
+
+/// Files that are text even when libgit2 takes them for binary, which happens to UTF-16.
+const TEXT_EXTENSIONS: &[&str] = &[
+    "bat", "c", "cfg", "cmd", "conf", "config", "cpp", "cs", "csproj", "css", "csv", "go", "h",
+    "hpp", "htm", "html", "inf", "ini", "java", "js", "json", "log", "md", "props", "ps1", "py",
+    "rb", "rc", "reg", "resx", "rs", "rst", "sh", "sln", "sql", "toml", "ts", "tsv", "txt",
+    "vcxproj", "xml", "yaml", "yml",
+];
+/// Files that are never text, so their content isn't worth a look.
+const BINARY_EXTENSIONS: &[&str] = &[
+    "7z", "a", "avi", "bmp", "bz2", "dll", "dylib", "exe", "flac", "gif", "gz", "ico", "jar",
+    "jpeg", "jpg", "lib", "mov", "mp3", "mp4", "o", "ogg", "otf", "pdb", "pdf", "png", "rar", "so",
+    "tar", "tif", "tiff", "ttf", "wav", "webp", "woff", "woff2", "xz", "zip",
+];
+/// Longest byte order mark, the one of UTF-8.
+const BOM_LENGTH: usize = 3;
+/// Bytes of a binary file checked for the zeros of UTF-16.
+const SNIFF_LENGTH: usize = 8 * 1024;
+
+/// The encoding a file is decoded with, only needed for files that aren't valid UTF-8.
+///
+/// An encoding declared with `working-tree-encoding` in `.gitattributes` wins, the flag tells
+/// whether that was the case. Otherwise it is guessed from a byte order mark or the content,
+/// binary files only turn out as UTF-16.
+pub(super) fn detect(repo: &Repository, delta: &DiffDelta) -> (&'static Encoding, bool) {
+    let path = delta.new_file().path().or(delta.old_file().path());
+    let declared = path.and_then(|path| {
+        repo.get_attr(
+            path,
+            "working-tree-encoding",
+            AttrCheckFlags::FILE_THEN_INDEX,
+        )
+        .ok()
+        .flatten()
+        .and_then(|label| Encoding::for_label(label.as_bytes()))
+    });
+    if let Some(encoding) = declared {
+        return (encoding, true);
+    }
+
+    let binary = delta.flags().is_binary();
+    let extension = path
+        .and_then(Path::extension)
+        .and_then(|e| e.to_str())
+        .map(str::to_lowercase);
+    let has_extension = |extensions: &[&str]| {
+        extension
+            .as_deref()
+            .is_some_and(|e| extensions.contains(&e))
+    };
+    if binary && has_extension(BINARY_EXTENSIONS) {
+        return (UTF_8, false);
+    }
+
+    // binary content only turns out as UTF-16, which takes a byte order mark unless the file is
+    // known to be text, and a few kilobytes are enough to tell
+    let text = has_extension(TEXT_EXTENSIONS);
+    let read = |file| {
+        if !binary {
+            super::content(repo, &file)
+        } else if text {
+            super::content_head(repo, &file, SNIFF_LENGTH)
+        } else {
+            super::content_head(repo, &file, BOM_LENGTH)
+        }
+    };
+    match read(delta.new_file()).or_else(|| read(delta.old_file())) {
+        Some(bytes) => (sniff(&bytes, binary, text), false),
+        None => (UTF_8, false),
+    }
+}
+
+/// Guesses the encoding of the bytes, binary content stays UTF-8 unless it has a byte order mark
+/// or looks like UTF-16 in a file known to be text.
+fn sniff(bytes: &[u8], binary: bool, text: bool) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+        return encoding;
+    }
+    // ASCII text in UTF-16 has every other byte zero, which would still be valid UTF-8
+    let zeros = |offset: usize| {
+        bytes
+            .iter()
+            .skip(offset)
+            .step_by(2)
+            .filter(|b| **b == 0)
+            .count()
+    };
+    let half = bytes.len() / 2;
+    if text && half > 0 && zeros(1) * 4 >= half && zeros(0) * 4 < half {
+        return UTF_16LE;
+    }
+    if text && half > 0 && zeros(0) * 4 >= half && zeros(1) * 4 < half {
+        return UTF_16BE;
+    }
+    if binary || std::str::from_utf8(bytes).is_ok() {
+        return UTF_8;
+    }
+
+    let mut detector = EncodingDetector::new();
+    detector.feed(bytes, true);
+    detector.guess(None, true)
+}
+
+/// Decodes a line, invalid bytes turn into U+FFFD. The flag tells whether that happened.
+///
+/// git keeps files with a declared working tree encoding as UTF-8 in the repository, so only
+/// lines from the working tree are in that encoding.
+pub(super) fn decode(encoding: &'static Encoding, declared: bool, bytes: &[u8]) -> (String, bool) {
+    if declared {
+        if let Ok(text) = std::str::from_utf8(bytes) {
+            return (text.to_owned(), false);
+        }
+    }
+
+    let (text, lossy) = encoding.decode_without_bom_handling(bytes);
+    (text.into_owned(), lossy)
+}
+
+/// Whether libgit2 can split the file into lines, it only knows ASCII compatible encodings.
+pub(super) fn is_ascii_compatible(encoding: &'static Encoding) -> bool {
+    encoding != UTF_16LE && encoding != UTF_16BE
+}
+
+/// Diffs the decoded content of both sides for files libgit2 considers binary.
+pub(super) fn diff_lines(
+    encoding: &'static Encoding,
+    old: &[u8],
+    new: &[u8],
+    settings: &DiffSettings,
+) -> (Vec<Header>, Vec<Line>, bool) {
+    let (old, _, old_lossy) = encoding.decode(old);
+    let (new, _, new_lossy) = encoding.decode(new);
+    let (old, old_newline) = algorithm::split(&old);
+    let (new, new_newline) = algorithm::split(&new);
+    let (headers, lines) =
+        algorithm::diff_lines((&old, old_newline), (&new, new_newline), settings);
+
+    (headers, lines, old_lossy || new_lossy)
+}
+
+#[cfg(test)]
+mod tests {
+    use encoding_rs::{SHIFT_JIS, WINDOWS_1252};
+
+    use super::*;
+
+    #[test]
+    fn sniff_encodings() {
+        assert_eq!(sniff("plain ascii".as_bytes(), false, true), UTF_8);
+        assert_eq!(sniff(b"\xef\xbb\xbfwith bom", false, false), UTF_8);
+        assert_eq!(sniff(b"\xff\xfeh\x00i\x00", true, false), UTF_16LE);
+        assert_eq!(sniff(b"h\x00e\x00l\x00l\x00o\x00", true, true), UTF_16LE);
+        assert_eq!(sniff(b"\x00h\x00e\x00l\x00l\x00o", true, true), UTF_16BE);
+        // 16 bit samples look the same, but aren't text
+        assert_eq!(
+            sniff(b"\x10\x00\x20\x00\x30\x00\x40\x00", true, false),
+            UTF_8
+        );
+
+        let (latin, _, _) = WINDOWS_1252.encode("Le café est très crémeux, à côté de la forêt.");
+        assert_eq!(sniff(&latin, false, false), WINDOWS_1252);
+        let (japanese, _, _) =
+            SHIFT_JIS.encode("これは日本語のテキストです。文字化けしないでください。");
+        assert_eq!(sniff(&japanese, false, false), SHIFT_JIS);
+    }
+}
@@ -0,0 +1,162 @@
+use super::commit::Commit;
+
+/// How the graph passes through the row of a single commit. Edges are pairs of lanes, the
+/// first at the top of the half row and the second at the bottom.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GraphRow {
+    /// The lane the commit itself sits in.
+    pub lane: usize,
+    /// Edges from the top of the row to the commit.
+    pub upper: Vec<(usize, usize)>,
+    /// Edges from the commit to the bottom of the row.
+    pub lower: Vec<(usize, usize)>,
+    /// Number of lanes used in this row.
+    pub width: usize,
+}
+
+/// Lanes of the commit graph, built up page by page as the log loads.
+#[derive(Debug, Clone, Default)]
+pub struct Graph {
+    pub rows: Vec<GraphRow>,
+    /// The commit each lane is waiting for.
+    lanes: Vec<Option<String>>,
+}
+
+impl Graph {
+    /// Assigns lanes to the next commits of a log in topological order, children before their
+    /// parents.
+    pub fn extend(&mut self, commits: &[Commit]) {
+        let lanes = &mut self.lanes;
+
+        for commit in commits {
+            let id = commit.id.as_str();
+            let lane = match lanes.iter().position(|l| l.as_deref() == Some(id)) {
+                Some(lane) => lane,
+                None => free_lane(lanes),
+            };
+
+            let mut upper = Vec::new();
+            for (i, waiting) in lanes.iter().enumerate() {
+                match waiting {
+                    Some(waiting) if waiting == id => upper.push((i, lane)),
+                    Some(_) => upper.push((i, i)),
+                    None => (),
+                }
+            }
+
+            // branches that end in this commit are merged into its lane
+            for waiting in lanes.iter_mut() {
+                if waiting.as_deref() == Some(id) {
+                    *waiting = None;
+                }
+            }
+
+            let mut lower = Vec::new();
+            for (i, waiting) in lanes.iter().enumerate() {
+                if waiting.is_some() {
+                    lower.push((i, i));
+                }
+            }
+
+            let mut parents = commit.parents.iter();
+            if let Some(first) = parents.next() {
+                match lanes.iter().position(|l| l.as_ref() == Some(first)) {
+                    Some(existing) => lower.push((lane, existing)),
+                    None => {
+                        lanes[lane] = Some(first.clone());
+                        lower.push((lane, lane));
+                    }
+                }
+            }
+            for parent in parents {
+                let target = match lanes.iter().position(|l| l.as_ref() == Some(parent)) {
+                    Some(existing) => existing,
+                    None => {
+                        let target = free_lane(lanes);
+                        lanes[target] = Some(parent.clone());
+                        target
+                    }
+                };
+                lower.push((lane, target));
+            }
+
+            while lanes.last() == Some(&None) {
+                lanes.pop();
+            }
+
+            let width = upper
+                .iter()
+                .chain(&lower)
+                .map(|(from, to)| from.max(to) + 1)
+                .max()
+                .unwrap_or(0)
+                .max(lane + 1);
+            self.rows.push(GraphRow {
+                lane,
+                upper,
+                lower,
+                width,
+            });
+        }
+    }
+}
+
+fn free_lane(lanes: &mut Vec<Option<String>>) -> usize {
+    match lanes.iter().position(Option::is_none) {
+        Some(lane) => lane,
+        None => {
+            lanes.push(None);
+            lanes.len() - 1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDateTime;
+
+    use super::*;
+    use crate::git::commit::Author;
+
+    fn commit(id: &str, parents: &[&str]) -> Commit {
+        Commit {
+            id: id.to_owned(),
+            author: Author {
+                name: String::new(),
+                email: String::new(),
+            },
+            message: String::new(),
+            time: NaiveDateTime::default(),
+            parents: parents.iter().map(|p| p.to_string()).collect(),
+            refs: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn branch_and_merge() {
+        // d merges c into b, both forked from a
+        let commits = [
+            commit("d", &["b", "c"]),
+            commit("c", &["a"]),
+            commit("b", &["a"]),
+            commit("a", &[]),
+        ];
+
+        // loaded in two pages
+        let mut graph = Graph::default();
+        graph.extend(&commits[..2]);
+        graph.extend(&commits[2..]);
+        let rows = graph.rows;
+
+        assert_eq!(rows[0].lane, 0);
+        assert_eq!(rows[0].lower, vec![(0, 0), (0, 1)]);
+        assert_eq!(rows[1].lane, 1);
+        assert_eq!(rows[1].upper, vec![(0, 0), (1, 1)]);
+        assert_eq!(rows[1].lower, vec![(0, 0), (1, 1)]);
+        assert_eq!(rows[2].lane, 0);
+        assert_eq!(rows[2].lower, vec![(1, 1), (0, 1)]);
+        assert_eq!(rows[3].lane, 1);
+        assert_eq!(rows[3].upper, vec![(1, 1)]);
+        assert_eq!(rows[3].width, 2);
+    }
+}
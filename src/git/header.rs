@@ -4,22 +4,52 @@ use anyhow::{Context, Result};
 pub struct Header {
     pub content: String,
     pub line: u32,
+    pub lines: u32,
+    pub old_line: u32,
+    pub old_lines: u32,
 }
 
 impl Header {
     pub fn new(raw: String) -> Result<Header> {
-        let line: u32 = raw
-            .split(' ')
-            .nth(2)
+        let mut ranges = raw.split(' ').skip(1);
+        let (old_line, old_lines) =
+            Header::parse_range(ranges.next().context("less elements than expected")?)?;
+        let (line, lines) =
+            Header::parse_range(ranges.next().context("less elements than expected")?)?;
+
+        Ok(Header {
+            content: raw,
+            line,
+            lines,
+            old_line,
+            old_lines,
+        })
+    }
+
+    // "-209,6" or "+222", the count defaults to 1 when omitted
+    fn parse_range(raw: &str) -> Result<(u32, u32)> {
+        let mut parts = raw
+            .get(1..)
             .context("less elements than expected")?
-            .split(',')
+            .split(',');
+        let start = parts
             .next()
             .context("less elements than expected")?
-            .get(1..)
-            .context("less elements than expected")?
             .parse()?;
+        let count = match parts.next() {
+            Some(count) => count.parse()?,
+            None => 1,
+        };
 
-        Ok(Header { content: raw, line })
+        Ok((start, count))
+    }
+
+    pub fn contains_old(&self, lineno: u32) -> bool {
+        lineno >= self.old_line && lineno < self.old_line + self.old_lines
+    }
+
+    pub fn contains_new(&self, lineno: u32) -> bool {
+        lineno >= self.line && lineno < self.line + self.lines
     }
 }
 
@@ -31,6 +61,13 @@ mod tests {
     fn parse_header() {
         let header =
             Header::new("@@ -209,6 +222,33 @@ impl fmt::Display for Diff {".to_string()).unwrap();
-        assert_eq!(header.line, 222)
+        assert_eq!(header.line, 222);
+        assert_eq!(header.lines, 33);
+        assert_eq!(header.old_line, 209);
+        assert_eq!(header.old_lines, 6);
+
+        let header = Header::new("@@ -1 +0,0 @@".to_string()).unwrap();
+        assert_eq!(header.old_lines, 1);
+        assert_eq!(header.lines, 0);
     }
 }
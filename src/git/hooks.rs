@@ -0,0 +1,66 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{bail, Result};
+use git2::Repository;
+
+/// Runs the hook with the given name if the repository has one, fails with its output if it
+/// exits unsuccessfully.
+pub(super) fn run(repo: &Repository, name: &str, args: &[&Path]) -> Result<()> {
+    let Some(hook) = find(repo, name)? else {
+        return Ok(());
+    };
+    let Some(workdir) = repo.workdir() else {
+        return Ok(());
+    };
+
+    let output = Command::new(&hook)
+        .args(args)
+        .current_dir(workdir)
+        .env("GIT_DIR", repo.path())
+        .output()?;
+    if !output.status.success() {
+        let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+        text.push_str(&String::from_utf8_lossy(&output.stderr));
+        bail!("{name} hook failed:\n{}", text.trim_end());
+    }
+
+    Ok(())
+}
+
+/// Passes the message through the `commit-msg` hook, which may change it.
+pub(super) fn commit_msg(repo: &Repository, message: &str) -> Result<String> {
+    let file = repo.path().join("COMMIT_EDITMSG");
+    fs::write(&file, message)?;
+    run(repo, "commit-msg", &[&file])?;
+    Ok(fs::read_to_string(file)?)
+}
+
+fn find(repo: &Repository, name: &str) -> Result<Option<PathBuf>> {
+    // a relative core.hooksPath is relative to the working tree, like git does it
+    let dir = match repo.config()?.get_path("core.hooksPath") {
+        Ok(dir) if dir.is_relative() => match repo.workdir() {
+            Some(workdir) => workdir.join(dir),
+            None => dir,
+        },
+        Ok(dir) => dir,
+        Err(_) => repo.path().join("hooks"),
+    };
+
+    let hook = dir.join(name);
+    Ok((hook.is_file() && is_executable(&hook)).then_some(hook))
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path).is_ok_and(|m| m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(_path: &Path) -> bool {
+    true
+}
@@ -0,0 +1,69 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{Context, Result};
+use git2::{DiffDelta, DiffFile, Oid, Repository};
+
+const EXTENSIONS: [&str; 5] = ["png", "jpg", "jpeg", "gif", "svg"];
+/// Larger images aren't previewed, decoding them would stall the diff.
+const MAX_SIZE: u64 = 20 * 1024 * 1024;
+
+/// Both sides of a changed image, `None` where the file doesn't exist.
+#[derive(Debug, Clone)]
+pub struct Images {
+    pub old: Option<Image>,
+    pub new: Option<Image>,
+}
+
+/// Where to find one side of an image, the content is only read once it is shown.
+#[derive(Debug, Clone, Hash)]
+pub struct Image {
+    pub id: Oid,
+    pub path: PathBuf,
+    /// Unique for the content and ending in the file name, so the image loaders can tell the
+    /// format and cache the decoded texture.
+    pub uri: String,
+}
+
+pub(super) fn load(delta: &DiffDelta) -> Option<Images> {
+    let path = delta.new_file().path().or(delta.old_file().path())?;
+    let extension = path.extension()?.to_str()?.to_lowercase();
+    if !EXTENSIONS.contains(&extension.as_str()) {
+        return None;
+    }
+
+    let images = Images {
+        old: side(&delta.old_file()),
+        new: side(&delta.new_file()),
+    };
+    (images.old.is_some() || images.new.is_some()).then_some(images)
+}
+
+/// Content of the image from the object database, or from the working tree for files that
+/// aren't in it.
+pub fn read(path: &String, image: &Image) -> Result<Arc<[u8]>> {
+    let repo = Repository::open(path)?;
+    if let Ok(blob) = repo.find_blob(image.id) {
+        return Ok(blob.content().into());
+    }
+
+    let workdir = repo.workdir().context("repository has no working tree")?;
+    Ok(fs::read(workdir.join(&image.path))?.into())
+}
+
+fn side(file: &DiffFile) -> Option<Image> {
+    let path = file.path()?;
+    if file.id().is_zero() || file.size() > MAX_SIZE {
+        return None;
+    }
+
+    let name = path.file_name().map(Path::new)?.to_str()?;
+    Some(Image {
+        id: file.id(),
+        path: path.to_path_buf(),
+        uri: format!("bytes://{}/{name}", file.id()),
+    })
+}
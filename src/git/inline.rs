@@ -0,0 +1,179 @@
+use std::ops::Range;
+
+use similar::{capture_diff_slices, Algorithm, DiffOp};
+
+use super::line::Line;
+
+// pairs sharing less than this fraction of their bytes are treated as unrelated lines
+const MIN_SIMILARITY: f32 = 0.3;
+
+/// Byte ranges that changed within each line, deleted and inserted lines of a hunk are paired
+/// up in order and compared word by word. Unpaired and context lines have no ranges.
+pub fn changed_spans(lines: &[Line]) -> Vec<Vec<Range<usize>>> {
+    let mut spans = vec![Vec::new(); lines.len()];
+    let mut deletions = Vec::new();
+    let mut insertions = Vec::new();
+
+    for (i, line) in lines.iter().enumerate() {
+        match line.origin {
+            '-' => deletions.push(i),
+            '+' => insertions.push(i),
+            _ => {
+                compare_pairs(lines, &deletions, &insertions, &mut spans);
+                deletions.clear();
+                insertions.clear();
+            }
+        }
+    }
+    compare_pairs(lines, &deletions, &insertions, &mut spans);
+
+    spans
+}
+
+fn compare_pairs(
+    lines: &[Line],
+    deletions: &[usize],
+    insertions: &[usize],
+    spans: &mut [Vec<Range<usize>>],
+) {
+    for (&old, &new) in deletions.iter().zip(insertions) {
+        if let Some((old_spans, new_spans)) = compare(&lines[old].content, &lines[new].content) {
+            spans[old] = old_spans;
+            spans[new] = new_spans;
+        }
+    }
+}
+
+type Spans = Vec<Range<usize>>;
+
+fn compare(old: &str, new: &str) -> Option<(Spans, Spans)> {
+    let old_tokens = tokenize(old);
+    let new_tokens = tokenize(new);
+    let old_words: Vec<&str> = old_tokens.iter().map(|r| &old[r.clone()]).collect();
+    let new_words: Vec<&str> = new_tokens.iter().map(|r| &new[r.clone()]).collect();
+
+    let mut old_spans: Spans = Vec::new();
+    let mut new_spans: Spans = Vec::new();
+    let mut unchanged = 0;
+
+    for op in capture_diff_slices(Algorithm::Myers, &old_words, &new_words) {
+        match op {
+            DiffOp::Equal { old_index, len, .. } => {
+                unchanged += byte_range(&old_tokens, old_index..old_index + len).len();
+            }
+            DiffOp::Delete {
+                old_index, old_len, ..
+            } => push_span(
+                &mut old_spans,
+                byte_range(&old_tokens, old_index..old_index + old_len),
+            ),
+            DiffOp::Insert {
+                new_index, new_len, ..
+            } => push_span(
+                &mut new_spans,
+                byte_range(&new_tokens, new_index..new_index + new_len),
+            ),
+            DiffOp::Replace {
+                old_index,
+                old_len,
+                new_index,
+                new_len,
+            } => {
+                push_span(
+                    &mut old_spans,
+                    byte_range(&old_tokens, old_index..old_index + old_len),
+                );
+                push_span(
+                    &mut new_spans,
+                    byte_range(&new_tokens, new_index..new_index + new_len),
+                );
+            }
+        }
+    }
+
+    let longest = old.len().max(new.len());
+    if longest == 0 || (unchanged as f32) / (longest as f32) < MIN_SIMILARITY {
+        return None;
+    }
+
+    Some((old_spans, new_spans))
+}
+
+fn byte_range(tokens: &[Range<usize>], range: Range<usize>) -> Range<usize> {
+    match (
+        tokens.get(range.start),
+        range.end.checked_sub(1).and_then(|i| tokens.get(i)),
+    ) {
+        (Some(first), Some(last)) if !range.is_empty() => first.start..last.end,
+        _ => 0..0,
+    }
+}
+
+// adjacent changes are merged so they are emphasized as a single block
+fn push_span(spans: &mut Spans, span: Range<usize>) {
+    if span.is_empty() {
+        return;
+    }
+
+    match spans.last_mut() {
+        Some(last) if last.end == span.start => last.end = span.end,
+        _ => spans.push(span),
+    }
+}
+
+/// Splits a line into words, runs of whitespace and single punctuation characters.
+fn tokenize(line: &str) -> Vec<Range<usize>> {
+    let mut tokens: Vec<Range<usize>> = Vec::new();
+    let mut last_kind = None;
+
+    for (i, c) in line.char_indices() {
+        let kind = if c.is_alphanumeric() || c == '_' {
+            Some(0)
+        } else if c.is_whitespace() {
+            Some(1)
+        } else {
+            None
+        };
+
+        match tokens.last_mut() {
+            Some(last) if kind.is_some() && kind == last_kind => last.end = i + c.len_utf8(),
+            _ => tokens.push(i..i + c.len_utf8()),
+        }
+        last_kind = kind;
+    }
+
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changed_word() {
+        let lines = vec![
+            Line::new(Some(1), None, "let count = 10;".to_string(), '-'),
+            Line::new(None, Some(1), "let count = 12;".to_string(), '+'),
+            Line::new(Some(2), Some(2), "}".to_string(), ' '),
+        ];
+
+        let spans = changed_spans(&lines);
+
+        assert_eq!(spans[0], vec![12..14]);
+        assert_eq!(spans[1], vec![12..14]);
+        assert!(spans[2].is_empty());
+    }
+
+    #[test]
+    fn unrelated_lines() {
+        let lines = vec![
+            Line::new(Some(1), None, "fn main() {".to_string(), '-'),
+            Line::new(None, Some(1), "// completely different".to_string(), '+'),
+        ];
+
+        let spans = changed_spans(&lines);
+
+        assert!(spans[0].is_empty());
+        assert!(spans[1].is_empty());
+    }
+}
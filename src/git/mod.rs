@@ -1,111 +1,194 @@
 use anyhow::Result;
 use core::fmt;
-use git2::Repository;
+use encoding_rs::{Encoding, UTF_8};
+use git2::{Delta, DiffDelta, DiffFile, Oid, Patch, Repository};
 use header::Header;
+use image::Images;
 use line::Line;
+use moved::Move;
+use revision::RevisionRange;
+use settings::{DiffAlgorithm, DiffSettings};
 use stats::Stats;
-use std::{cell::RefCell, path::PathBuf, rc::Rc};
+use std::{
+    cell::RefCell,
+    collections::HashSet,
+    fs,
+    io::Read,
+    ops::Range,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+use text::DiffText;
 
+mod algorithm;
+pub mod blame;
+pub mod branch;
 pub mod commit;
+pub mod conflict;
+pub mod context;
+pub mod discard;
+mod encoding;
+pub mod graph;
 mod header;
+mod hooks;
+pub mod image;
+mod inline;
 mod line;
+pub mod moved;
+pub mod revision;
+pub mod search;
+pub mod settings;
+pub mod stage;
+pub mod stash;
 pub mod stats;
+pub mod text;
 
 #[derive(Debug, Clone)]
 pub struct Diff {
     old_file: PathBuf,
     new_file: PathBuf,
+    pub status: Delta,
+    /// Similarity in percent for renamed and copied files.
+    pub similarity: u16,
     pub headers: Vec<Header>,
+    /// Range of `lines` belonging to each of the `headers`.
+    pub hunks: Vec<Range<usize>>,
     pub lines: Vec<Line>,
-    pub content: String,
-    pub origins_content: String,
-    pub line_numbers: String,
-    pub header_indices: Vec<usize>,
-    pub insertion_indices: Vec<usize>,
-    pub deletion_indices: Vec<usize>,
-    pub neutral_indices: Vec<usize>,
+    /// Blob of the new side, zero if there is none.
+    pub new_id: Oid,
+    /// Set for binary files, they have no lines.
+    pub binary: Option<Binary>,
+    /// Content of both sides for image formats that can be previewed.
+    pub images: Option<Images>,
+    /// What the lines were decoded from.
+    pub encoding: &'static Encoding,
+    /// Whether some bytes weren't valid in the encoding and show as U+FFFD.
+    pub lossy: bool,
+    /// Set when the encoding comes from `working-tree-encoding`, the repository has the file in
+    /// UTF-8 then.
+    pub declared_encoding: bool,
+    /// Blocks moved here or away from here, see `moved::detect`.
+    pub moves: Vec<Move>,
+    pub unified: DiffText,
+    pub left: DiffText,
+    pub right: DiffText,
 }
 
 impl Diff {
-    fn new(old_file: PathBuf, new_file: PathBuf, headers: Vec<Header>, lines: Vec<Line>) -> Diff {
-        let mut longest_line = 0;
-        for line in &lines {
-            let line_no = match line.origin {
-                '+' => line.new_lineno.unwrap_or(0),
-                '-' => line.old_lineno.unwrap_or(0),
-                _ => line.new_lineno.unwrap_or(0),
-            };
-
-            if line_no > longest_line {
-                longest_line = line_no;
-            }
-        }
-
-        let longest_line_len = longest_line.to_string().len();
-
-        let mut content = "".to_owned();
-        let mut origins_content = "".to_owned();
-        let mut lines_content = "".to_owned();
-        let mut header_indices = Vec::new();
-        let mut insertion_indices = Vec::new();
-        let mut deletion_indices = Vec::new();
-        let mut neutral_indices = Vec::new();
-
-        let mut i = 0;
-        for line in &lines {
-            for header in &headers {
-                if header.line == line.new_lineno.unwrap_or(0)
-                    && line.origin != '+'
-                    && line.origin != '-'
-                {
-                    content.push_str(format!("{}\n", header.content).as_str());
-                    origins_content.push_str(" \n");
-                    lines_content.push_str(" \n");
-                    header_indices.push(i);
-                    i += 1;
-                }
-            }
-            let mut line_no = match line.origin {
-                '+' => line.new_lineno.unwrap_or(0).to_string(),
-                '-' => line.old_lineno.unwrap_or(0).to_string(),
-                _ => line.new_lineno.unwrap_or(0).to_string(),
-            };
-
-            while line_no.len() != longest_line_len {
-                line_no = format!(" {}", line_no);
-            }
-
-            content.push_str(format!("{}\n", line.content.as_str()).as_str());
-            origins_content.push_str(format!("{} \n", line.origin).as_str());
-            lines_content.push_str(format!("{}\n", line_no).as_str());
-
-            match line.origin {
-                '+' => insertion_indices.push(i),
-                '-' => deletion_indices.push(i),
-                _ => neutral_indices.push(i),
-            };
-
-            i += 1;
-        }
+    fn new(
+        old_file: PathBuf,
+        new_file: PathBuf,
+        status: Delta,
+        headers: Vec<Header>,
+        lines: Vec<Line>,
+    ) -> Diff {
+        let spans = inline::changed_spans(&lines);
+        let hunks = hunk_ranges(&headers, &lines);
+        let unified = DiffText::unified(&headers, &hunks, &lines, &spans);
+        let (left, right) = DiffText::split(&headers, &hunks, &lines, &spans);
 
         Diff {
             old_file,
             new_file,
+            status,
+            similarity: 0,
             headers,
+            hunks,
             lines,
-            content,
-            origins_content,
-            line_numbers: lines_content,
-            header_indices,
-            insertion_indices,
-            deletion_indices,
-            neutral_indices,
+            new_id: Oid::zero(),
+            binary: None,
+            images: None,
+            encoding: UTF_8,
+            lossy: false,
+            declared_encoding: false,
+            moves: Vec::new(),
+            unified,
+            left,
+            right,
         }
     }
 
     pub fn file_name(&self) -> PathBuf {
+        self.new_file.to_owned()
+    }
+
+    pub fn old_file_name(&self) -> PathBuf {
         self.old_file.to_owned()
     }
+
+    pub fn is_rename(&self) -> bool {
+        matches!(self.status, Delta::Renamed | Delta::Copied)
+    }
+
+    /// "old/path → new/path (87%)" for renames and copies, the path otherwise.
+    pub fn label(&self) -> String {
+        if !self.is_rename() {
+            return self
+                .new_file
+                .to_str()
+                .unwrap_or("Error fetching file name")
+                .to_owned();
+        }
+
+        rename_label(&self.old_file, &self.new_file, self.similarity)
+    }
+}
+
+/// "old/path → new/path (87%)"
+pub fn rename_label(old_file: &Path, new_file: &Path, similarity: u16) -> String {
+    format!(
+        "{} → {} ({}%)",
+        old_file.to_str().unwrap_or("Error fetching file name"),
+        new_file.to_str().unwrap_or("Error fetching file name"),
+        similarity
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Binary {
+    /// Size in bytes, 0 if the file doesn't exist on that side.
+    pub old_size: u64,
+    pub new_size: u64,
+}
+
+impl Binary {
+    fn new(delta: &DiffDelta) -> Option<Binary> {
+        delta.flags().is_binary().then(|| Binary {
+            old_size: delta.old_file().size(),
+            new_size: delta.new_file().size(),
+        })
+    }
+}
+
+fn hunk_ranges(headers: &[Header], lines: &[Line]) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Option<Range<usize>>> = vec![None; headers.len()];
+    let mut hunk = 0;
+
+    for (i, line) in lines.iter().enumerate() {
+        while let Some(next) = headers.get(hunk + 1) {
+            let in_next = line.old_lineno.is_some_and(|n| next.contains_old(n))
+                || line.new_lineno.is_some_and(|n| next.contains_new(n));
+            let in_current = line
+                .old_lineno
+                .is_some_and(|n| headers[hunk].contains_old(n))
+                || line
+                    .new_lineno
+                    .is_some_and(|n| headers[hunk].contains_new(n));
+
+            if in_next && !in_current {
+                hunk += 1;
+            } else {
+                break;
+            }
+        }
+
+        if let Some(range) = ranges.get_mut(hunk) {
+            let start = range.as_ref().map_or(i, |r| r.start);
+            *range = Some(start..i + 1);
+        }
+    }
+
+    ranges.into_iter().map(|r| r.unwrap_or(0..0)).collect()
 }
 
 impl fmt::Display for Diff {
@@ -125,57 +208,140 @@ impl fmt::Display for Diff {
     }
 }
 
-pub fn get_staged_diffs(path: &String) -> Result<(Vec<Diff>, Stats)> {
+pub fn get_staged_diffs(path: &String, settings: &DiffSettings) -> Result<(Vec<Diff>, Stats)> {
     let repo = Repository::open(path)?;
     let head = repo.head()?.peel_to_tree()?;
-    let diffs = repo.diff_tree_to_index(Some(&head), None, None)?;
-    parse_diffs(diffs)
+    let mut diffs = repo.diff_tree_to_index(Some(&head), None, Some(&mut settings.options()))?;
+    settings.find_similar(&mut diffs)?;
+    parse_diffs(&repo, diffs, settings)
 }
 
-pub fn get_diffs(path: &String) -> Result<(Vec<Diff>, Stats)> {
+pub fn get_diffs(path: &String, settings: &DiffSettings) -> Result<(Vec<Diff>, Stats)> {
     let repo = Repository::open(path)?;
-    let diffs = repo.diff_index_to_workdir(None, None)?;
-    parse_diffs(diffs)
+    let mut options = settings.options();
+    options
+        .include_untracked(true)
+        .show_untracked_content(true)
+        .recurse_untracked_dirs(settings.recurse_untracked_dirs);
+    let mut diffs = repo.diff_index_to_workdir(None, Some(&mut options))?;
+    settings.find_similar(&mut diffs)?;
+    parse_diffs(&repo, diffs, settings)
+}
+
+pub fn get_revision_diffs(
+    path: &String,
+    range: &RevisionRange,
+    settings: &DiffSettings,
+) -> Result<(Vec<Diff>, Stats)> {
+    let repo = Repository::open(path)?;
+    let (old_tree, new_tree) = range.trees(&repo)?;
+    let mut diffs = repo.diff_tree_to_tree(
+        Some(&old_tree),
+        Some(&new_tree),
+        Some(&mut settings.options()),
+    )?;
+    settings.find_similar(&mut diffs)?;
+    parse_diffs(&repo, diffs, settings)
+}
+
+/// Diffs a commit against one of its parents, root commits are diffed against the empty tree.
+pub fn get_commit_diffs(
+    path: &String,
+    id: &str,
+    parent: usize,
+    settings: &DiffSettings,
+) -> Result<(Vec<Diff>, Stats)> {
+    let repo = Repository::open(path)?;
+    let commit = repo.find_commit(Oid::from_str(id)?)?;
+    let new_tree = commit.tree()?;
+    let old_tree = match commit.parent_count() {
+        0 => None,
+        _ => Some(commit.parent(parent)?.tree()?),
+    };
+    let mut diffs = repo.diff_tree_to_tree(
+        old_tree.as_ref(),
+        Some(&new_tree),
+        Some(&mut settings.options()),
+    )?;
+    settings.find_similar(&mut diffs)?;
+    parse_diffs(&repo, diffs, settings)
+}
+
+/// Diffs a stash against the commit it was made on, including the untracked files it stashed.
+pub fn get_stash_diffs(
+    path: &String,
+    id: &str,
+    settings: &DiffSettings,
+) -> Result<(Vec<Diff>, Stats)> {
+    let repo = Repository::open(path)?;
+    let stash = repo.find_commit(Oid::from_str(id)?)?;
+    let mut diffs = repo.diff_tree_to_tree(
+        Some(&stash.parent(0)?.tree()?),
+        Some(&stash.tree()?),
+        Some(&mut settings.options()),
+    )?;
+
+    let mut untracked = HashSet::new();
+    if let Some(commit) = stash::untracked_commit(&repo, stash.id())? {
+        let untracked_diffs =
+            repo.diff_tree_to_tree(None, Some(&commit.tree()?), Some(&mut settings.options()))?;
+        untracked.extend(
+            untracked_diffs
+                .deltas()
+                .filter_map(|d| d.new_file().path().map(|p| p.to_path_buf())),
+        );
+        diffs.merge(&untracked_diffs)?;
+    }
+    settings.find_similar(&mut diffs)?;
+
+    let (mut diffs, stats) = parse_diffs(&repo, diffs, settings)?;
+    for diff in &mut diffs {
+        if diff.status == Delta::Added && untracked.contains(&diff.new_file) {
+            diff.status = Delta::Untracked;
+        }
+    }
+
+    Ok((diffs, stats))
 }
 
-fn parse_diffs(diffs: git2::Diff) -> Result<(Vec<Diff>, Stats)> {
-    let line_groups = Rc::new(RefCell::new(Vec::new()));
+fn parse_diffs(
+    repo: &Repository,
+    diffs: git2::Diff,
+    settings: &DiffSettings,
+) -> Result<(Vec<Diff>, Stats)> {
+    // binary files only have their flag set once the content is loaded for the lines, lines
+    // keep their bytes until the encoding of the file is known
+    let line_groups: Rc<RefCell<Vec<Vec<RawLine>>>> = Rc::new(RefCell::new(Vec::new()));
+    let binaries = Rc::new(RefCell::new(Vec::new()));
     diffs.foreach(
         &mut |_delta, _num| {
             line_groups.borrow_mut().push(Vec::new());
+            binaries.borrow_mut().push(Binary::new(&_delta));
             true
         },
+        Some(&mut |_delta, _binary| {
+            if let Some(last) = binaries.borrow_mut().last_mut() {
+                *last = Binary::new(&_delta);
+            }
+            true
+        }),
         None,
-        None,
-        Some(
-            &mut |_delta, _hunk, _line| match std::str::from_utf8(_line.content()) {
-                Ok(c) => {
-                    let mut content = c.to_string();
-                    if content.ends_with('\n') {
-                        content.pop();
-                        if content.ends_with('\r') {
-                            content.pop();
-                        }
-                    }
+        Some(&mut |_delta, _hunk, _line| {
+            let line = RawLine {
+                old_lineno: _line.old_lineno(),
+                new_lineno: _line.new_lineno(),
+                content: _line.content().to_vec(),
+                origin: _line.origin(),
+            };
 
-                    let line = Line::new(
-                        _line.old_lineno(),
-                        _line.new_lineno(),
-                        content,
-                        _line.origin(),
-                    );
-
-                    match line_groups.borrow_mut().last_mut() {
-                        Some(last) => {
-                            last.push(line);
-                            true
-                        }
-                        None => false,
-                    }
+            match line_groups.borrow_mut().last_mut() {
+                Some(last) => {
+                    last.push(line);
+                    true
                 }
-                Err(_) => false,
-            },
-        ),
+                None => false,
+            }
+        }),
     )?;
 
     let header_groups = Rc::new(RefCell::new(Vec::new()));
@@ -185,34 +351,30 @@ fn parse_diffs(diffs: git2::Diff) -> Result<(Vec<Diff>, Stats)> {
             true
         },
         None,
-        Some(
-            &mut |_delta, _hunk| match std::str::from_utf8(_hunk.header()) {
-                Ok(c) => {
-                    let mut content = c.to_string();
-                    if content.ends_with('\n') {
-                        content.pop();
-                        if content.ends_with('\r') {
-                            content.pop();
-                        }
-                    }
+        Some(&mut |_delta, _hunk| {
+            let mut content = String::from_utf8_lossy(_hunk.header()).into_owned();
+            if content.ends_with('\n') {
+                content.pop();
+                if content.ends_with('\r') {
+                    content.pop();
+                }
+            }
 
-                    match Header::new(content) {
-                        Ok(header) => match header_groups.borrow_mut().last_mut() {
-                            Some(last) => {
-                                last.push(header);
-                                true
-                            }
-                            None => false,
-                        },
-                        Err(_) => false,
+            match Header::new(content) {
+                Ok(header) => match header_groups.borrow_mut().last_mut() {
+                    Some(last) => {
+                        last.push(header);
+                        true
                     }
-                }
+                    None => false,
+                },
                 Err(_) => false,
-            },
-        ),
+            }
+        }),
         None,
     )?;
 
+    let similarities = similarities(&diffs)?;
     let mut result = Vec::new();
     diffs.foreach(
         &mut |_delta, _num| {
@@ -233,12 +395,73 @@ fn parse_diffs(diffs: git2::Diff) -> Result<(Vec<Diff>, Stats)> {
                 return false;
             };
 
-            let diff = Diff::new(
+            let binary = binaries.borrow().get(result.len()).copied().flatten();
+            let utf8 = lines
+                .iter()
+                .all(|l| std::str::from_utf8(&l.content).is_ok());
+            let (encoding, declared) = if binary.is_none() && utf8 {
+                (UTF_8, false)
+            } else {
+                encoding::detect(repo, &_delta)
+            };
+
+            // deltas without hunks have nothing the histogram diff could match up differently
+            let histogram = match binary {
+                None if settings.algorithm == DiffAlgorithm::Histogram && !headers.is_empty() => {
+                    histogram(repo, &_delta, (encoding, declared), settings)
+                }
+                _ => None,
+            };
+            let (headers, lines, lossy) = match (binary, histogram) {
+                // libgit2 doesn't split UTF-16 into lines, so it is diffed after decoding
+                (Some(_), _) if !encoding::is_ascii_compatible(encoding) => encoding::diff_lines(
+                    encoding,
+                    &content(repo, &_delta.old_file()).unwrap_or_default(),
+                    &content(repo, &_delta.new_file()).unwrap_or_default(),
+                    settings,
+                ),
+                (_, Some(diffed)) => diffed,
+                _ => {
+                    let mut lossy = false;
+                    let lines = lines
+                        .iter()
+                        .map(|l| {
+                            let (line, line_lossy) = l.decode(encoding, declared);
+                            lossy |= line_lossy;
+                            line
+                        })
+                        .collect();
+                    (headers.to_vec(), lines, lossy)
+                }
+            };
+            let (headers, lines) = match settings.expanded.get(new_file) {
+                Some(expanded) if binary.is_none() && expanded.id == _delta.new_file().id() => {
+                    let content = content(repo, &_delta.new_file()).unwrap_or_default();
+                    let content: Vec<String> = content
+                        .split(|b| *b == b'\n')
+                        .map(|l| encoding::decode(encoding, declared, l).0)
+                        .collect();
+                    context::expand(&headers, &lines, &content, &expanded.lines)
+                }
+                _ => (headers, lines),
+            };
+
+            let mut diff = Diff::new(
                 old_file.to_path_buf(),
                 new_file.to_path_buf(),
-                headers.to_vec(),
-                lines.to_vec(),
+                _delta.status(),
+                headers,
+                lines,
             );
+            diff.similarity = similarities.get(result.len()).copied().unwrap_or(0);
+            if encoding::is_ascii_compatible(encoding) {
+                diff.binary = binary;
+            }
+            diff.new_id = _delta.new_file().id();
+            diff.encoding = encoding;
+            diff.lossy = lossy;
+            diff.declared_encoding = declared;
+            diff.images = image::load(&_delta);
             result.push(diff);
 
             hg.remove(0);
@@ -250,5 +473,118 @@ fn parse_diffs(diffs: git2::Diff) -> Result<(Vec<Diff>, Stats)> {
         None,
     )?;
 
-    Ok((result, Stats::new(diffs.stats()?)))
+    moved::detect(&mut result);
+    // libgit2 counts the lines of its own algorithm, which aren't the ones shown for histogram
+    let stats = match settings.algorithm {
+        DiffAlgorithm::Histogram => Stats::from_diffs(&result),
+        _ => Stats::new(diffs.stats()?),
+    };
+    Ok((result, stats))
+}
+
+struct RawLine {
+    old_lineno: Option<u32>,
+    new_lineno: Option<u32>,
+    content: Vec<u8>,
+    origin: char,
+}
+
+impl RawLine {
+    fn decode(&self, encoding: &'static Encoding, declared: bool) -> (Line, bool) {
+        let (mut content, lossy) = encoding::decode(encoding, declared, &self.content);
+        // a CR stays, patches for CRLF files need it and it can be shown
+        if content.ends_with('\n') {
+            content.pop();
+        }
+
+        let line = Line::new(self.old_lineno, self.new_lineno, content, self.origin);
+        (line, lossy)
+    }
+}
+
+/// Content of one side of a delta, working tree files aren't in the object database.
+fn content(repo: &Repository, file: &DiffFile) -> Option<Vec<u8>> {
+    let path = file.path()?;
+    if file.id().is_zero() {
+        return None;
+    }
+
+    match repo.find_blob(file.id()) {
+        Ok(blob) => Some(blob.content().to_vec()),
+        Err(_) => fs::read(repo.workdir()?.join(path)).ok(),
+    }
+}
+
+/// Like `content`, but reads at most `limit` bytes where the storage allows it.
+fn content_head(repo: &Repository, file: &DiffFile, limit: usize) -> Option<Vec<u8>> {
+    let path = file.path()?;
+    if file.id().is_zero() {
+        return None;
+    }
+
+    let mut bytes = Vec::new();
+    // packed objects can't be streamed, they are read whole
+    if let Ok((reader, _, _)) = repo.odb().ok()?.reader(file.id()) {
+        reader.take(limit as u64).read_to_end(&mut bytes).ok()?;
+        return Some(bytes);
+    }
+    match repo.find_blob(file.id()) {
+        Ok(blob) => Some(blob.content()[..blob.size().min(limit)].to_vec()),
+        Err(_) => {
+            let file = fs::File::open(repo.workdir()?.join(path)).ok()?;
+            file.take(limit as u64).read_to_end(&mut bytes).ok()?;
+            Some(bytes)
+        }
+    }
+}
+
+/// Diffs both sides of a text file in-process with the histogram algorithm, `None` if a side
+/// can't be read.
+fn histogram(
+    repo: &Repository,
+    delta: &DiffDelta,
+    (encoding, declared): (&'static Encoding, bool),
+    settings: &DiffSettings,
+) -> Option<(Vec<Header>, Vec<Line>, bool)> {
+    let side = |file: DiffFile| {
+        if file.exists() {
+            content(repo, &file).map(|bytes| encoding::decode(encoding, declared, &bytes))
+        } else {
+            Some((String::new(), false))
+        }
+    };
+    let (old, old_lossy) = side(delta.old_file())?;
+    let (new, new_lossy) = side(delta.new_file())?;
+
+    let (old, old_newline) = algorithm::split(&old);
+    let (new, new_newline) = algorithm::split(&new);
+    let (headers, lines) =
+        algorithm::diff_lines((&old, old_newline), (&new, new_newline), settings);
+    Some((headers, lines, old_lossy || new_lossy))
+}
+
+// git2 doesn't expose the similarity of a delta, but it is part of the patch header
+fn similarities(diffs: &git2::Diff) -> Result<Vec<u16>> {
+    let mut similarities = Vec::new();
+    for (i, delta) in diffs.deltas().enumerate() {
+        if !matches!(delta.status(), Delta::Renamed | Delta::Copied) {
+            similarities.push(0);
+            continue;
+        }
+
+        let similarity = match Patch::from_diff(diffs, i)? {
+            Some(mut patch) => patch
+                .to_buf()?
+                .as_str()
+                .unwrap_or("")
+                .lines()
+                .find_map(|l| l.strip_prefix("similarity index "))
+                .and_then(|s| s.trim_end_matches('%').parse().ok())
+                .unwrap_or(0),
+            None => 0,
+        };
+        similarities.push(similarity);
+    }
+
+    Ok(similarities)
 }
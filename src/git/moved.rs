@@ -0,0 +1,204 @@
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Range,
+    path::PathBuf,
+};
+
+use super::Diff;
+
+/// Blocks with fewer letters and digits than this aren't worth pointing out, same as git.
+const MIN_ALNUM: usize = 20;
+/// Lines like `}` or `end` occur everywhere, blocks are only looked up by lines with at least
+/// this many letters and digits and grown over the others.
+const ANCHOR_ALNUM: usize = 5;
+/// Lines inserted more often than this are no anchors either, trying them all would be
+/// quadratic.
+const MAX_CANDIDATES: usize = 16;
+
+/// A block of deleted lines that shows up again as inserted lines, in this or another diff.
+#[derive(Debug, Clone)]
+pub struct Move {
+    /// Range of `Diff::lines` holding the block.
+    pub lines: Range<usize>,
+    /// Whether this is where the block was deleted.
+    pub deleted: bool,
+    /// The diff holding the other occurrence and its range of lines there.
+    pub other_file: PathBuf,
+    pub other_lines: Range<usize>,
+    /// Line number of the other occurrence, on the side it exists on.
+    pub other_line_number: u32,
+}
+
+/// Finds moved blocks across the diffs and records them in both diffs and their texts.
+///
+/// Lines are compared without their indentation, so code moved into another scope counts.
+/// Changes within the same hunk are left out, those are edits rather than moves.
+pub(super) fn detect(diffs: &mut [Diff]) {
+    let mut insertions: HashMap<&str, Vec<(usize, usize)>> = HashMap::new();
+    for (d, diff) in diffs.iter().enumerate() {
+        for (i, line) in diff.lines.iter().enumerate() {
+            if line.origin == '+' && alnum(std::slice::from_ref(line)) >= ANCHOR_ALNUM {
+                insertions
+                    .entry(line.content.trim())
+                    .or_default()
+                    .push((d, i));
+            }
+        }
+    }
+
+    // deleting diff, deleted lines, inserting diff, start of the inserted lines
+    let mut blocks = Vec::new();
+    let mut taken = HashSet::new();
+    for (d, diff) in diffs.iter().enumerate() {
+        // lines from here on aren't part of a block yet
+        let mut free = 0;
+        let mut i = 0;
+        while i < diff.lines.len() {
+            let candidates = match diff.lines[i].origin {
+                '-' => insertions
+                    .get(diff.lines[i].content.trim())
+                    .filter(|candidates| candidates.len() <= MAX_CANDIDATES),
+                _ => None,
+            };
+
+            // inserting diff, start of the deleted and of the inserted lines and length of the
+            // longest block, which can start before the line it was found by
+            let mut best: Option<(usize, usize, usize, usize)> = None;
+            for &(other, j) in candidates.into_iter().flatten() {
+                if other == d && hunk(diff, i) == hunk(diff, j) {
+                    continue;
+                }
+                let fits = |k: usize, l: usize| {
+                    !taken.contains(&(other, l)) && matches(&diffs[d], k, &diffs[other], l)
+                };
+                let before = (1..=(i - free).min(j))
+                    .take_while(|&k| fits(i - k, j - k))
+                    .count();
+                let after = (0..).take_while(|&k| fits(i + k, j + k)).count();
+                let len = before + after;
+                if len > best.map(|(_, _, _, len)| len).unwrap_or(0) {
+                    best = Some((other, i - before, j - before, len));
+                }
+            }
+
+            match best {
+                Some((other, i, j, len)) if alnum(&diff.lines[i..i + len]) >= MIN_ALNUM => {
+                    taken.extend((j..j + len).map(|j| (other, j)));
+                    blocks.push((d, i..i + len, other, j));
+                    free = i + len;
+                }
+                _ => (),
+            }
+            i = free.max(i + 1);
+        }
+    }
+
+    for (d, deleted, other, j) in blocks {
+        let inserted = j..j + deleted.len();
+        let from = Move {
+            lines: deleted.clone(),
+            deleted: true,
+            other_file: diffs[other].file_name(),
+            other_lines: inserted.clone(),
+            other_line_number: diffs[other].lines[j].new_lineno.unwrap_or(0),
+        };
+        let to = Move {
+            lines: inserted,
+            deleted: false,
+            other_file: diffs[d].file_name(),
+            other_lines: deleted.clone(),
+            other_line_number: diffs[d].lines[deleted.start].old_lineno.unwrap_or(0),
+        };
+
+        for (diff, block) in [(d, from), (other, to)] {
+            let diff = &mut diffs[diff];
+            diff.unified.mark_moved(&block.lines);
+            diff.left.mark_moved(&block.lines);
+            diff.right.mark_moved(&block.lines);
+            diff.moves.push(block);
+        }
+    }
+}
+
+fn matches(diff: &Diff, i: usize, other: &Diff, j: usize) -> bool {
+    match (diff.lines.get(i), other.lines.get(j)) {
+        (Some(deleted), Some(inserted)) => {
+            deleted.origin == '-'
+                && inserted.origin == '+'
+                && deleted.content.trim() == inserted.content.trim()
+        }
+        _ => false,
+    }
+}
+
+fn hunk(diff: &Diff, line: usize) -> Option<usize> {
+    diff.hunks.iter().position(|range| range.contains(&line))
+}
+
+fn alnum(lines: &[super::line::Line]) -> usize {
+    lines
+        .iter()
+        .flat_map(|line| line.content.chars())
+        .filter(|c| c.is_alphanumeric())
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use git2::Delta;
+
+    use super::*;
+    use crate::git::{header::Header, line::Line};
+
+    fn diff(name: &str, header: &str, lines: Vec<Line>) -> Diff {
+        Diff::new(
+            PathBuf::from(name),
+            PathBuf::from(name),
+            Delta::Modified,
+            vec![Header::new(header.to_string()).unwrap()],
+            lines,
+        )
+    }
+
+    #[test]
+    fn detect_across_files() {
+        // the closing brace is no anchor, the block grows back over it
+        let body = [
+            "}",
+            "fn moved_function() {",
+            "    do_something_useful();",
+            "}",
+        ];
+        let mut diffs = vec![
+            diff(
+                "a.rs",
+                "@@ -1,5 +1,1 @@",
+                std::iter::once(Line::new(Some(1), Some(1), "keep".to_string(), ' '))
+                    .chain(
+                        body.iter()
+                            .enumerate()
+                            .map(|(i, l)| Line::new(Some(i as u32 + 2), None, l.to_string(), '-')),
+                    )
+                    .collect(),
+            ),
+            diff(
+                "b.rs",
+                "@@ -1,0 +1,4 @@",
+                body.iter()
+                    .enumerate()
+                    .map(|(i, l)| Line::new(None, Some(i as u32 + 1), format!("    {l}"), '+'))
+                    .collect(),
+            ),
+        ];
+
+        detect(&mut diffs);
+
+        assert_eq!(diffs[0].moves.len(), 1);
+        assert!(diffs[0].moves[0].deleted);
+        assert_eq!(diffs[0].moves[0].lines, 1..5);
+        assert_eq!(diffs[0].moves[0].other_file, PathBuf::from("b.rs"));
+        assert_eq!(diffs[1].moves[0].other_lines, 1..5);
+        assert_eq!(diffs[1].moves[0].other_line_number, 2);
+        assert_eq!(diffs[0].unified.moved_indices, vec![2, 3, 4, 5]);
+    }
+}
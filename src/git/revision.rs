@@ -0,0 +1,95 @@
+use core::fmt;
+
+use anyhow::Result;
+use git2::{Repository, Tree};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RevisionRange {
+    pub from: String,
+    pub to: String,
+    pub merge_base: bool,
+}
+
+impl RevisionRange {
+    /// Parses `<rev>..<rev>` and `<rev>...<rev>`, an omitted side defaults to `HEAD`.
+    pub fn parse(raw: &str) -> Option<RevisionRange> {
+        let (from, to, merge_base) = match raw.split_once("...") {
+            Some((from, to)) => (from, to, true),
+            None => {
+                let (from, to) = raw.split_once("..")?;
+                (from, to, false)
+            }
+        };
+
+        Some(RevisionRange::new(from, to, merge_base))
+    }
+
+    pub fn new(from: &str, to: &str, merge_base: bool) -> RevisionRange {
+        let or_head = |rev: &str| {
+            if rev.trim().is_empty() {
+                "HEAD".to_owned()
+            } else {
+                rev.trim().to_owned()
+            }
+        };
+
+        RevisionRange {
+            from: or_head(from),
+            to: or_head(to),
+            merge_base,
+        }
+    }
+
+    pub fn trees<'a>(&self, repo: &'a Repository) -> Result<(Tree<'a>, Tree<'a>)> {
+        let from = repo.revparse_single(&self.from)?.peel_to_commit()?;
+        let to = repo.revparse_single(&self.to)?.peel_to_commit()?;
+
+        let from = if self.merge_base {
+            repo.find_commit(repo.merge_base(from.id(), to.id())?)?
+        } else {
+            from
+        };
+
+        Ok((from.tree()?, to.tree()?))
+    }
+
+    /// Checks that both revisions, and their merge base if needed, can be found in the repository.
+    pub fn resolve(&self, path: &String) -> Result<()> {
+        let repo = Repository::open(path)?;
+        self.trees(&repo)?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for RevisionRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.merge_base {
+            write!(f, "{}...{}", self.from, self.to)
+        } else {
+            write!(f, "{}..{}", self.from, self.to)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_range() {
+        let range = RevisionRange::parse("main..feature").unwrap();
+        assert_eq!(range.from, "main");
+        assert_eq!(range.to, "feature");
+        assert!(!range.merge_base);
+
+        let range = RevisionRange::parse("main...HEAD~3").unwrap();
+        assert_eq!(range.from, "main");
+        assert_eq!(range.to, "HEAD~3");
+        assert!(range.merge_base);
+
+        let range = RevisionRange::parse("v0.9.0..").unwrap();
+        assert_eq!(range.to, "HEAD");
+
+        assert!(RevisionRange::parse("main").is_none());
+    }
+}
@@ -0,0 +1,232 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use git2::{DiffOptions, Repository};
+use regex::{Regex, RegexBuilder};
+
+use super::commit::{self, Commit};
+
+/// A log search like `author:anna since:2024-01-01 path:src -S"fn main" fix.*crash`. Words
+/// without a prefix form a case-insensitive regex over the author and the message.
+#[derive(Debug, Clone, Default)]
+pub struct Query {
+    /// Matched against name and email, any of them has to match.
+    pub authors: Vec<String>,
+    pub since: Option<NaiveDateTime>,
+    pub until: Option<NaiveDateTime>,
+    /// The commit has to change something below one of these paths.
+    pub paths: Vec<PathBuf>,
+    /// The commit has to change how often this string occurs in a file.
+    pub pickaxe: Option<String>,
+    pub pattern: Option<Regex>,
+}
+
+impl Query {
+    pub fn parse(query: &str) -> Result<Query, String> {
+        let mut result = Query::default();
+        let mut words = Vec::new();
+
+        let mut tokens = tokens(query).into_iter();
+        while let Some(token) = tokens.next() {
+            if let Some(author) = token.strip_prefix("author:") {
+                result.authors.push(author.to_lowercase());
+            } else if let Some(date) = token.strip_prefix("since:") {
+                result.since = Some(parse_date(date)?.and_time(NaiveTime::MIN));
+            } else if let Some(date) = token.strip_prefix("until:") {
+                let end_of_day = NaiveTime::from_hms_opt(23, 59, 59).unwrap_or_default();
+                result.until = Some(parse_date(date)?.and_time(end_of_day));
+            } else if let Some(path) = token.strip_prefix("path:") {
+                result.paths.push(PathBuf::from(path));
+            } else if let Some(string) = token.strip_prefix("-S") {
+                // both "-Sfoo" and "-S foo"
+                let string = if string.is_empty() {
+                    tokens.next().ok_or("-S needs a string to search for")?
+                } else {
+                    string.to_owned()
+                };
+                result.pickaxe = Some(string);
+            } else {
+                words.push(token);
+            }
+        }
+
+        if !words.is_empty() {
+            let words = words.join(" ");
+            // a half typed regex is still useful as plain text
+            let pattern = RegexBuilder::new(&words)
+                .case_insensitive(true)
+                .build()
+                .or_else(|_| {
+                    RegexBuilder::new(&regex::escape(&words))
+                        .case_insensitive(true)
+                        .build()
+                })
+                .map_err(|e| e.to_string())?;
+            result.pattern = Some(pattern);
+        }
+
+        Ok(result)
+    }
+
+    /// Checks everything that doesn't need the repository.
+    pub fn matches(&self, commit: &Commit) -> bool {
+        let author_matches = self.authors.is_empty()
+            || self.authors.iter().any(|a| {
+                commit.author.name.to_lowercase().contains(a)
+                    || commit.author.email.to_lowercase().contains(a)
+            });
+        let pattern_matches = match &self.pattern {
+            Some(p) => {
+                p.is_match(&commit.message)
+                    || p.is_match(&commit.author.name)
+                    || p.is_match(&commit.author.email)
+            }
+            None => true,
+        };
+
+        let in_range = match (self.since, self.until) {
+            (Some(since), _) if commit.time < since => false,
+            (_, Some(until)) if commit.time > until => false,
+            _ => true,
+        };
+
+        author_matches && pattern_matches && in_range
+    }
+
+    fn needs_diff(&self) -> bool {
+        !self.paths.is_empty() || self.pickaxe.is_some()
+    }
+}
+
+/// Walks the log like `commit::walk_log` and keeps the commits matching the query. `on_page`
+/// gets the matches every `batch_size` walked commits, so it can stop the search even while
+/// nothing is found.
+pub fn search_log(
+    path: &String,
+    all_branches: bool,
+    query: &Query,
+    batch_size: usize,
+    mut on_page: impl FnMut(Vec<Commit>, bool) -> bool,
+) -> Result<()> {
+    let repo = Repository::open(path)?;
+    let mut references = commit::references(&repo)?;
+    let mut commits = Vec::new();
+
+    for (i, id) in commit::revwalk(&repo, all_branches)?.enumerate() {
+        if i > 0 && i % batch_size == 0 && !on_page(std::mem::take(&mut commits), false) {
+            return Ok(());
+        }
+
+        let id = id?;
+        let git_commit = repo.find_commit(id)?;
+        let refs = references.remove(&id).unwrap_or_default();
+        let commit = Commit::new(&git_commit, refs);
+        if !query.matches(&commit) {
+            continue;
+        }
+
+        if query.needs_diff() && !diff_matches(&repo, &git_commit, query)? {
+            continue;
+        }
+
+        commits.push(commit);
+    }
+
+    on_page(commits, true);
+    Ok(())
+}
+
+/// Checks the path and pickaxe filters against the changes of a commit to its first parent.
+fn diff_matches(repo: &Repository, commit: &git2::Commit, query: &Query) -> Result<bool> {
+    let mut options = DiffOptions::new();
+    for path in &query.paths {
+        options.pathspec(path);
+    }
+
+    let old_tree = match commit.parent_count() {
+        0 => None,
+        _ => Some(commit.parent(0)?.tree()?),
+    };
+    let diff =
+        repo.diff_tree_to_tree(old_tree.as_ref(), Some(&commit.tree()?), Some(&mut options))?;
+    if diff.deltas().len() == 0 {
+        return Ok(false);
+    }
+
+    let Some(pickaxe) = &query.pickaxe else {
+        return Ok(true);
+    };
+
+    for delta in diff.deltas() {
+        let occurrences = |id: git2::Oid| -> Result<usize> {
+            if id.is_zero() {
+                return Ok(0);
+            }
+            let blob = repo.find_blob(id)?;
+            Ok(String::from_utf8_lossy(blob.content())
+                .matches(pickaxe.as_str())
+                .count())
+        };
+
+        if occurrences(delta.old_file().id())? != occurrences(delta.new_file().id())? {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+/// Splits at whitespace outside of double quotes, the quotes are removed.
+fn tokens(query: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut token = String::new();
+    let mut quoted = false;
+
+    for c in query.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !token.is_empty() {
+                    tokens.push(std::mem::take(&mut token));
+                }
+            }
+            c => token.push(c),
+        }
+    }
+
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+
+    tokens
+}
+
+fn parse_date(date: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| format!("Invalid date: {date}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_query() {
+        let query =
+            Query::parse(r#"author:Anna since:2024-01-02 path:src/ui -S "fn main" fix.*crash"#)
+                .unwrap();
+
+        assert_eq!(query.authors, vec!["anna"]);
+        assert_eq!(
+            query.since,
+            NaiveDate::from_ymd_opt(2024, 1, 2).map(|d| d.and_time(NaiveTime::MIN))
+        );
+        assert_eq!(query.until, None);
+        assert_eq!(query.paths, vec![PathBuf::from("src/ui")]);
+        assert_eq!(query.pickaxe.as_deref(), Some("fn main"));
+        assert!(query.pattern.unwrap().is_match("Fix the CRASH"));
+
+        assert!(Query::parse("since:yesterday").is_err());
+        assert!(Query::parse("unclosed(").unwrap().pattern.is_some());
+    }
+}
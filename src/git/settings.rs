@@ -0,0 +1,160 @@
+use std::{collections::HashMap, ops::Range, path::PathBuf};
+
+use anyhow::Result;
+use git2::{DiffFindOptions, DiffOptions, Oid};
+
+/// Options used for every diff that gets loaded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffSettings {
+    pub recurse_untracked_dirs: bool,
+    /// Minimum similarity in percent for a file to count as renamed or copied.
+    pub similarity_threshold: u16,
+    pub ignore_whitespace: bool,
+    pub ignore_whitespace_change: bool,
+    pub ignore_whitespace_eol: bool,
+    pub ignore_blank_lines: bool,
+    /// Unchanged lines shown around each change.
+    pub context_lines: u32,
+    /// Lines of the new side shown beyond the context lines, by file.
+    pub expanded: HashMap<PathBuf, Expanded>,
+    pub algorithm: DiffAlgorithm,
+}
+
+/// Lines revealed in one version of a file, they are dropped once its content changes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expanded {
+    /// The new side the lines were revealed in.
+    pub id: Oid,
+    /// Sorted ranges that neither overlap nor touch.
+    pub lines: Vec<Range<u32>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum DiffAlgorithm {
+    #[default]
+    Myers,
+    /// Myers that spends extra time to find the smallest diff.
+    Minimal,
+    Patience,
+    /// Computed in-process, libgit2 doesn't have it.
+    Histogram,
+}
+
+impl DiffAlgorithm {
+    pub const ALL: [DiffAlgorithm; 4] = [
+        DiffAlgorithm::Myers,
+        DiffAlgorithm::Minimal,
+        DiffAlgorithm::Patience,
+        DiffAlgorithm::Histogram,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DiffAlgorithm::Myers => "Myers",
+            DiffAlgorithm::Minimal => "Minimal",
+            DiffAlgorithm::Patience => "Patience",
+            DiffAlgorithm::Histogram => "Histogram",
+        }
+    }
+}
+
+impl Default for DiffSettings {
+    fn default() -> Self {
+        DiffSettings {
+            recurse_untracked_dirs: false,
+            // same as git
+            similarity_threshold: 50,
+            ignore_whitespace: false,
+            ignore_whitespace_change: false,
+            ignore_whitespace_eol: false,
+            ignore_blank_lines: false,
+            context_lines: 3,
+            expanded: HashMap::new(),
+            algorithm: DiffAlgorithm::default(),
+        }
+    }
+}
+
+impl DiffSettings {
+    /// Reveals more lines of the new side of a file with the given id.
+    pub fn expand(&mut self, file: PathBuf, id: Oid, lines: Range<u32>) {
+        let expanded = self.expanded.entry(file).or_insert_with(|| Expanded {
+            id,
+            lines: Vec::new(),
+        });
+        if expanded.id != id {
+            expanded.id = id;
+            expanded.lines.clear();
+        }
+
+        expanded.lines.push(lines);
+        expanded.lines.sort_by_key(|range| range.start);
+        let mut merged: Vec<Range<u32>> = Vec::new();
+        for range in expanded.lines.drain(..) {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+        expanded.lines = merged;
+    }
+
+    pub fn options(&self) -> DiffOptions {
+        let mut options = DiffOptions::new();
+        options
+            .context_lines(self.context_lines)
+            .minimal(self.algorithm == DiffAlgorithm::Minimal)
+            .patience(self.algorithm == DiffAlgorithm::Patience)
+            .ignore_whitespace(self.ignore_whitespace)
+            .ignore_whitespace_change(self.ignore_whitespace_change)
+            .ignore_whitespace_eol(self.ignore_whitespace_eol)
+            .ignore_blank_lines(self.ignores_blank_lines());
+        options
+    }
+
+    /// The histogram diff runs in-process and doesn't know how to ignore blank lines.
+    pub fn ignores_blank_lines(&self) -> bool {
+        self.ignore_blank_lines && self.algorithm != DiffAlgorithm::Histogram
+    }
+
+    /// Hunks don't match the files anymore when changes are hidden, so they can't be applied.
+    pub fn ignores_whitespace(&self) -> bool {
+        self.ignore_whitespace
+            || self.ignore_whitespace_change
+            || self.ignore_whitespace_eol
+            || self.ignores_blank_lines()
+    }
+
+    pub fn find_similar(&self, diffs: &mut git2::Diff) -> Result<()> {
+        let mut options = DiffFindOptions::new();
+        options
+            .renames(true)
+            .copies(true)
+            .for_untracked(true)
+            .rename_threshold(self.similarity_threshold)
+            .copy_threshold(self.similarity_threshold);
+        diffs.find_similar(Some(&mut options))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expand_merges_and_drops_stale_lines() {
+        let mut settings = DiffSettings::default();
+        let file = PathBuf::from("a");
+        let id = Oid::from_str("1").unwrap();
+        settings.expand(file.clone(), id, 20..30);
+        settings.expand(file.clone(), id, 5..10);
+        settings.expand(file.clone(), id, 10..22);
+        settings.expand(file.clone(), id, 40..45);
+        assert_eq!(settings.expanded[&file].lines, vec![5..30, 40..45]);
+
+        let changed = Oid::from_str("2").unwrap();
+        settings.expand(file.clone(), changed, 1..3);
+        assert_eq!(settings.expanded[&file].lines, vec![1..3]);
+    }
+}
@@ -0,0 +1,221 @@
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use git2::{ApplyLocation, Delta, IndexAddOption, ObjectType, Repository};
+
+use super::{encoding, Diff};
+
+pub fn stage_file(path: &String, file: &Path) -> Result<()> {
+    let repo = Repository::open(path)?;
+    let mut index = repo.index()?;
+
+    let full_path = Path::new(path).join(file);
+    if full_path.is_dir() {
+        // untracked directories are listed as a whole unless they are recursed
+        index.add_all([file], IndexAddOption::DEFAULT, None)?;
+    } else if full_path.exists() {
+        index.add_path(file)?;
+    } else {
+        index.remove_path(file)?;
+    }
+
+    index.write()?;
+    Ok(())
+}
+
+pub fn unstage_file(path: &String, file: &Path) -> Result<()> {
+    let repo = Repository::open(path)?;
+
+    match repo.head() {
+        Ok(head) => {
+            let commit = head.peel(ObjectType::Commit)?;
+            repo.reset_default(Some(&commit), [file])?;
+        }
+        // nothing has been committed yet, so unstaging means removing it from the index
+        Err(_) => {
+            let mut index = repo.index()?;
+            index.remove_path(file)?;
+            index.write()?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Stages the given lines of a diff between index and working tree.
+pub fn stage_lines(path: &String, diff: &Diff, selected: &[usize]) -> Result<()> {
+    let patch = patch(diff, selected, false).context("no changes selected")?;
+    apply_to_index(path, &encode(diff, &patch)?)
+}
+
+/// Unstages the given lines of a diff between HEAD and index.
+pub fn unstage_lines(path: &String, diff: &Diff, selected: &[usize]) -> Result<()> {
+    let patch = patch(diff, selected, true).context("no changes selected")?;
+    apply_to_index(path, &encode(diff, &patch)?)
+}
+
+fn apply_to_index(path: &String, patch: &[u8]) -> Result<()> {
+    let repo = Repository::open(path)?;
+    let diff = git2::Diff::from_buffer(patch)?;
+    repo.apply(&diff, ApplyLocation::Index, None)?;
+    Ok(())
+}
+
+/// Converts a patch built by `patch` back to the encoding of the file, so its lines match.
+pub(super) fn encode(diff: &Diff, patch: &str) -> Result<Vec<u8>> {
+    if diff.lossy {
+        bail!("some lines couldn't be decoded, only the whole file can be changed");
+    }
+    if !encoding::is_ascii_compatible(diff.encoding) || diff.declared_encoding {
+        bail!(
+            "{} files can only be changed as a whole",
+            diff.encoding.name()
+        );
+    }
+
+    let (patch, _, _) = diff.encoding.encode(patch);
+    Ok(patch.into_owned())
+}
+
+/// Builds a patch against the index containing only the selected lines of the diff.
+///
+/// Unselected removals turn into context and unselected additions are dropped. When `reverse`
+/// is set the diff is undone instead, so its additions are the lines present in the index.
+pub fn patch(diff: &Diff, selected: &[usize], reverse: bool) -> Option<String> {
+    let name = diff.file_name();
+    let name = name.to_str()?;
+    let (present, absent) = if reverse { ('+', '-') } else { ('-', '+') };
+
+    let old_name = diff.old_file_name();
+    let old_name = old_name.to_str()?;
+    let kind = match diff.status {
+        Delta::Renamed => "rename",
+        _ => "copy",
+    };
+
+    // untracked files don't exist in the index yet, so they have to be created there, and the
+    // index only knows the old path of renamed and copied files
+    let mut patch = if !reverse && diff.status == Delta::Untracked {
+        format!("diff --git a/{name} b/{name}\nnew file mode 100644\n--- /dev/null\n+++ b/{name}\n")
+    } else if !reverse && diff.is_rename() {
+        // libgit2 only parses these with the similarity and index lines around them
+        format!(
+            "diff --git a/{old_name} b/{name}\nsimilarity index {}%\n{kind} from {old_name}\n{kind} to {name}\nindex 0000000..0000000\n--- a/{old_name}\n+++ b/{name}\n",
+            diff.similarity
+        )
+    } else {
+        format!("diff --git a/{name} b/{name}\n--- a/{name}\n+++ b/{name}\n")
+    };
+    let mut offset: i64 = 0;
+    let mut changed = false;
+
+    for (header, range) in diff.headers.iter().zip(&diff.hunks) {
+        let start = if reverse {
+            header.line
+        } else {
+            header.old_line
+        };
+
+        let mut body = String::new();
+        let mut old_count: i64 = 0;
+        let mut new_count: i64 = 0;
+        let mut hunk_changed = false;
+        let mut last_emitted = false;
+
+        for i in range.clone() {
+            let line = &diff.lines[i];
+            let is_selected = selected.contains(&i);
+
+            let origin = match line.origin {
+                ' ' => Some(' '),
+                o if o == present && is_selected => Some('-'),
+                o if o == present => Some(' '),
+                o if o == absent && is_selected => Some('+'),
+                o if o == absent => None,
+                // "no newline at end of file" markers belong to the line before them
+                _ => {
+                    if last_emitted {
+                        body.push_str("\\ No newline at end of file\n");
+                    }
+                    continue;
+                }
+            };
+
+            last_emitted = origin.is_some();
+            let Some(origin) = origin else {
+                continue;
+            };
+
+            match origin {
+                ' ' => {
+                    old_count += 1;
+                    new_count += 1;
+                }
+                '-' => old_count += 1,
+                _ => new_count += 1,
+            }
+            hunk_changed |= origin != ' ';
+            body.push_str(&format!("{}{}\n", origin, line.content));
+        }
+
+        if !hunk_changed {
+            continue;
+        }
+        changed = true;
+
+        // a hunk without old lines is inserted after its start line
+        let new_start = start as i64 + offset + if old_count == 0 { 1 } else { 0 };
+        patch.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            start, old_count, new_start, new_count
+        ));
+        patch.push_str(&body);
+        offset += new_count - old_count;
+    }
+
+    changed.then_some(patch)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::git::{header::Header, line::Line};
+
+    fn diff() -> Diff {
+        Diff::new(
+            PathBuf::from("src/main.rs"),
+            PathBuf::from("src/main.rs"),
+            Delta::Modified,
+            vec![Header::new("@@ -1,3 +1,3 @@".to_string()).unwrap()],
+            vec![
+                Line::new(Some(1), Some(1), "a".to_string(), ' '),
+                Line::new(Some(2), None, "b".to_string(), '-'),
+                Line::new(None, Some(2), "c".to_string(), '+'),
+                Line::new(Some(3), Some(3), "d".to_string(), ' '),
+            ],
+        )
+    }
+
+    #[test]
+    fn stage_addition_only() {
+        let patch = patch(&diff(), &[2], false).unwrap();
+        assert_eq!(
+            patch,
+            "diff --git a/src/main.rs b/src/main.rs\n--- a/src/main.rs\n+++ b/src/main.rs\n\
+             @@ -1,3 +1,4 @@\n a\n b\n+c\n d\n"
+        );
+    }
+
+    #[test]
+    fn unstage_removal_only() {
+        let patch = patch(&diff(), &[1], true).unwrap();
+        assert!(patch.ends_with("@@ -1,3 +1,4 @@\n a\n+b\n c\n d\n"));
+    }
+
+    #[test]
+    fn nothing_selected() {
+        assert!(patch(&diff(), &[0, 3], false).is_none());
+    }
+}
@@ -0,0 +1,90 @@
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use git2::{Oid, Repository, StashApplyOptions, StashFlags};
+
+#[derive(Debug, Clone)]
+pub struct Stash {
+    /// Position in the stash list, `stash@{index}`.
+    pub index: usize,
+    pub id: String,
+    pub message: String,
+    pub time: NaiveDateTime,
+}
+
+pub fn get_stashes(path: &String) -> Result<Vec<Stash>> {
+    let mut repo = Repository::open(path)?;
+
+    let mut entries = Vec::new();
+    repo.stash_foreach(|index, message, id| {
+        entries.push((index, message.to_owned(), *id));
+        true
+    })?;
+
+    let mut stashes = Vec::new();
+    for (index, message, id) in entries {
+        let time = repo.find_commit(id)?.time();
+        stashes.push(Stash {
+            index,
+            id: id.to_string(),
+            message,
+            time: NaiveDateTime::from_timestamp_opt(
+                time.seconds() + time.offset_minutes() as i64 * 60,
+                0,
+            )
+            .unwrap_or_default(),
+        });
+    }
+
+    Ok(stashes)
+}
+
+/// Stashes all changes of the index and working tree, the author comes from the git config.
+pub fn save(path: &String, message: &str, include_untracked: bool) -> Result<()> {
+    let mut repo = Repository::open(path)?;
+    let signature = repo.signature()?;
+    let flags = if include_untracked {
+        StashFlags::INCLUDE_UNTRACKED
+    } else {
+        StashFlags::DEFAULT
+    };
+    let message = Some(message).filter(|m| !m.is_empty());
+
+    repo.stash_save2(&signature, message, Some(flags))?;
+    Ok(())
+}
+
+pub fn apply(path: &String, index: usize) -> Result<()> {
+    let mut repo = Repository::open(path)?;
+    repo.stash_apply(
+        index,
+        Some(&mut StashApplyOptions::new().reinstantiate_index()),
+    )?;
+    Ok(())
+}
+
+pub fn pop(path: &String, index: usize) -> Result<()> {
+    let mut repo = Repository::open(path)?;
+    repo.stash_pop(
+        index,
+        Some(&mut StashApplyOptions::new().reinstantiate_index()),
+    )?;
+    Ok(())
+}
+
+pub fn drop(path: &String, index: usize) -> Result<()> {
+    let mut repo = Repository::open(path)?;
+    repo.stash_drop(index)?;
+    Ok(())
+}
+
+/// The commit holding the untracked files of a stash, if they were stashed too.
+pub(super) fn untracked_commit<'repo>(
+    repo: &'repo Repository,
+    id: Oid,
+) -> Result<Option<git2::Commit<'repo>>> {
+    let stash = repo.find_commit(id)?;
+    match stash.parent_count() {
+        3 => Ok(Some(stash.parent(2)?)),
+        _ => Ok(None),
+    }
+}
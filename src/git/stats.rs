@@ -1,5 +1,7 @@
 use git2::DiffStats;
 
+use super::Diff;
+
 #[derive(Debug, Clone)]
 pub struct Stats {
     pub files_changed: usize,
@@ -15,4 +17,20 @@ impl Stats {
             deletions: diff_stats.deletions(),
         }
     }
+
+    /// Counts the lines the diffs show, for when they don't come from libgit2's own diff.
+    pub fn from_diffs(diffs: &[Diff]) -> Stats {
+        let count = |origin| {
+            diffs
+                .iter()
+                .flat_map(|diff| &diff.lines)
+                .filter(|line| line.origin == origin)
+                .count()
+        };
+        Stats {
+            files_changed: diffs.len(),
+            insertions: count('+'),
+            deletions: count('-'),
+        }
+    }
 }
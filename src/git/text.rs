@@ -0,0 +1,268 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    ops::Range,
+};
+
+use super::{header::Header, line::Line};
+
+/// Displayable text of a diff, split into the columns rendered by the diff area.
+#[derive(Debug, Clone, Default)]
+pub struct DiffText {
+    id: u64,
+    pub content: String,
+    pub origins_content: String,
+    pub line_numbers: String,
+    pub header_indices: Vec<usize>,
+    /// Index into `Diff::headers` for each of the `header_indices`.
+    pub hunk_indices: Vec<usize>,
+    pub insertion_indices: Vec<usize>,
+    pub deletion_indices: Vec<usize>,
+    pub neutral_indices: Vec<usize>,
+    /// Rows of deletions and insertions that belong to a moved block.
+    pub moved_indices: Vec<usize>,
+    /// Changed byte ranges within each row, see `inline::changed_spans`.
+    pub emphasis: Vec<Vec<Range<usize>>>,
+    /// Index into `Diff::lines` for each row, `None` for header and filler rows.
+    pub line_indices: Vec<Option<usize>>,
+    pub rows: usize,
+}
+
+// the text is used as key of the highlighting caches on every frame, hashing all of it each
+// time would be wasteful
+impl Hash for DiffText {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+impl DiffText {
+    pub fn unified(
+        headers: &[Header],
+        hunks: &[Range<usize>],
+        lines: &[Line],
+        spans: &[Vec<Range<usize>>],
+    ) -> DiffText {
+        let width = lines
+            .iter()
+            .map(|line| match line.origin {
+                '-' => line.old_lineno.unwrap_or(0),
+                _ => line.new_lineno.unwrap_or(0),
+            })
+            .max()
+            .unwrap_or(0)
+            .to_string()
+            .len();
+
+        let mut text = DiffText::default();
+        for (i, (line, spans)) in lines.iter().zip(spans).enumerate() {
+            if let Some(hunk) = hunk_at(hunks, i) {
+                text.push_header(hunk, &headers[hunk]);
+            }
+
+            let line_no = match line.origin {
+                '-' => line.old_lineno.unwrap_or(0),
+                _ => line.new_lineno.unwrap_or(0),
+            };
+            text.push_line(i, Some(line_no), line, spans, width);
+        }
+
+        text.identify();
+        text
+    }
+
+    /// Old and new side of the diff, deletions and insertions of a hunk are paired up row by
+    /// row and the shorter side is padded with filler rows.
+    pub fn split(
+        headers: &[Header],
+        hunks: &[Range<usize>],
+        lines: &[Line],
+        spans: &[Vec<Range<usize>>],
+    ) -> (DiffText, DiffText) {
+        let width = lines
+            .iter()
+            .filter_map(|line| line.old_lineno.max(line.new_lineno))
+            .max()
+            .unwrap_or(0)
+            .to_string()
+            .len();
+
+        let mut left = DiffText::default();
+        let mut right = DiffText::default();
+        let mut deletions: Vec<usize> = Vec::new();
+        let mut insertions: Vec<usize> = Vec::new();
+
+        for (i, line) in lines.iter().enumerate() {
+            // changes never pair up across hunks
+            if let Some(hunk) = hunk_at(hunks, i) {
+                let changes = (deletions.as_slice(), insertions.as_slice());
+                DiffText::push_changes(&mut left, &mut right, lines, spans, changes, width);
+                deletions.clear();
+                insertions.clear();
+
+                left.push_header(hunk, &headers[hunk]);
+                right.push_header(hunk, &headers[hunk]);
+            }
+
+            match line.origin {
+                '-' => deletions.push(i),
+                '+' => insertions.push(i),
+                _ => {
+                    let changes = (deletions.as_slice(), insertions.as_slice());
+                    DiffText::push_changes(&mut left, &mut right, lines, spans, changes, width);
+                    deletions.clear();
+                    insertions.clear();
+
+                    left.push_line(i, line.old_lineno, line, &spans[i], width);
+                    right.push_line(i, line.new_lineno, line, &spans[i], width);
+                }
+            }
+        }
+        let changes = (deletions.as_slice(), insertions.as_slice());
+        DiffText::push_changes(&mut left, &mut right, lines, spans, changes, width);
+
+        left.identify();
+        right.identify();
+        (left, right)
+    }
+
+    /// Marks the rows showing the given range of `Diff::lines` as moved.
+    pub(super) fn mark_moved(&mut self, lines: &Range<usize>) {
+        for (row, index) in self.line_indices.iter().enumerate() {
+            if index.is_some_and(|i| lines.contains(&i)) {
+                self.moved_indices.push(row);
+            }
+        }
+        self.moved_indices.sort_unstable();
+        self.identify();
+    }
+
+    /// Hashes everything shown into the id.
+    fn identify(&mut self) {
+        let mut hasher = DefaultHasher::new();
+        (
+            &self.content,
+            &self.origins_content,
+            &self.line_numbers,
+            &self.header_indices,
+            &self.insertion_indices,
+            &self.deletion_indices,
+            &self.moved_indices,
+            &self.emphasis,
+        )
+            .hash(&mut hasher);
+        self.id = hasher.finish();
+    }
+
+    fn push_changes(
+        left: &mut DiffText,
+        right: &mut DiffText,
+        lines: &[Line],
+        spans: &[Vec<Range<usize>>],
+        (deletions, insertions): (&[usize], &[usize]),
+        width: usize,
+    ) {
+        for i in 0..deletions.len().max(insertions.len()) {
+            match deletions.get(i) {
+                Some(&d) => left.push_line(d, lines[d].old_lineno, &lines[d], &spans[d], width),
+                None => left.push_filler(),
+            }
+            match insertions.get(i) {
+                Some(&n) => right.push_line(n, lines[n].new_lineno, &lines[n], &spans[n], width),
+                None => right.push_filler(),
+            }
+        }
+    }
+
+    fn push_header(&mut self, hunk: usize, header: &Header) {
+        self.content
+            .push_str(format!("{}\n", header.content).as_str());
+        self.origins_content.push_str(" \n");
+        self.line_numbers.push_str(" \n");
+        self.header_indices.push(self.rows);
+        self.hunk_indices.push(hunk);
+        self.emphasis.push(Vec::new());
+        self.line_indices.push(None);
+        self.rows += 1;
+    }
+
+    fn push_line(
+        &mut self,
+        index: usize,
+        line_no: Option<u32>,
+        line: &Line,
+        spans: &[Range<usize>],
+        width: usize,
+    ) {
+        let line_no = line_no.map(|n| n.to_string()).unwrap_or_default();
+
+        self.content
+            .push_str(format!("{}\n", line.content).as_str());
+        self.origins_content
+            .push_str(format!("{} \n", line.origin).as_str());
+        self.line_numbers
+            .push_str(format!("{:>width$}\n", line_no).as_str());
+        self.emphasis.push(spans.to_vec());
+        self.line_indices.push(Some(index));
+
+        match line.origin {
+            '+' => self.insertion_indices.push(self.rows),
+            '-' => self.deletion_indices.push(self.rows),
+            _ => self.neutral_indices.push(self.rows),
+        };
+
+        self.rows += 1;
+    }
+
+    fn push_filler(&mut self) {
+        self.content.push('\n');
+        self.origins_content.push_str(" \n");
+        self.line_numbers.push_str(" \n");
+        self.neutral_indices.push(self.rows);
+        self.emphasis.push(Vec::new());
+        self.line_indices.push(None);
+        self.rows += 1;
+    }
+}
+
+/// The hunk starting at the given line, if any.
+fn hunk_at(hunks: &[Range<usize>], line: usize) -> Option<usize> {
+    hunks
+        .iter()
+        .position(|range| range.start == line && !range.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_pairs_changes() {
+        let headers = vec![Header::new("@@ -1,3 +1,4 @@".to_string()).unwrap()];
+        let lines = vec![
+            Line::new(Some(1), Some(1), "a".to_string(), ' '),
+            Line::new(Some(2), None, "b".to_string(), '-'),
+            Line::new(None, Some(2), "c".to_string(), '+'),
+            Line::new(None, Some(3), "d".to_string(), '+'),
+            Line::new(Some(3), Some(4), "e".to_string(), ' '),
+        ];
+
+        let spans = vec![Vec::new(); lines.len()];
+        let hunks = [Range {
+            start: 0,
+            end: lines.len(),
+        }];
+        let (left, right) = DiffText::split(&headers, &hunks, &lines, &spans);
+
+        assert_eq!(left.rows, right.rows);
+        assert_eq!(left.content, "@@ -1,3 +1,4 @@\na\nb\n\ne\n");
+        assert_eq!(right.content, "@@ -1,3 +1,4 @@\na\nc\nd\ne\n");
+        assert_eq!(left.deletion_indices, vec![2]);
+        assert_eq!(right.insertion_indices, vec![2, 3]);
+        assert_eq!(left.line_numbers, " \n1\n2\n \n3\n");
+        assert_eq!(
+            left.line_indices,
+            vec![None, Some(0), Some(1), None, Some(4)]
+        );
+    }
+}
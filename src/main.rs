@@ -5,7 +5,17 @@ use std::{
     thread::{self},
 };
 
-use data::{AppData, ControlData, DiffType, Message};
+use data::{
+    AppData, AppDataCreationError, Blame, BlameSelection, CommitRequest, ControlData, DiffType,
+    FileLog, FileLogPage, FileLogSelection, LoadOptions, Log, LogPage, Message, Search, SearchPage,
+    SearchSelection,
+};
+use git::{
+    blame::{self, BlameTarget},
+    commit,
+    revision::RevisionRange,
+    search::{self, Query},
+};
 
 use eframe::egui;
 use egui::{Context, FontData, FontDefinitions, FontFamily};
@@ -26,10 +36,15 @@ fn main() -> Result<(), eframe::Error> {
     let options = eframe::NativeOptions::default();
 
     let path = get_initial_path();
+    let revision_range = get_initial_revision_range();
     eframe::run_native(
         "Contrast",
         options,
-        Box::new(move |_cc| Box::new(MyApp::new(path, profiler))),
+        Box::new(move |cc| {
+            // previews of changed images
+            egui_extras::install_image_loaders(&cc.egui_ctx);
+            Box::new(MyApp::new(path, revision_range, profiler))
+        }),
     )
 }
 
@@ -45,8 +60,21 @@ fn get_initial_path() -> Option<PathBuf> {
     })
 }
 
+fn get_initial_revision_range() -> Option<RevisionRange> {
+    env::args().nth(2).and_then(|r| {
+        RevisionRange::parse(&r).or_else(|| {
+            eprintln!("Invalid revision range: {r}");
+            None
+        })
+    })
+}
+
 struct MyApp {
     app_data: Option<AppData>,
+    log: Log,
+    file_log: Option<FileLog>,
+    blame: Option<Blame>,
+    search: Option<Search>,
     control_data: ControlData,
     sender: Sender<Message>,
     receiver: Receiver<Message>,
@@ -55,21 +83,47 @@ struct MyApp {
 }
 
 impl MyApp {
-    fn new(path: Option<PathBuf>, profiler: bool) -> MyApp {
+    fn new(path: Option<PathBuf>, revision_range: Option<RevisionRange>, profiler: bool) -> MyApp {
         let (sender, receiver) = mpsc::channel();
 
+        let load_options = LoadOptions {
+            revision_range: revision_range.clone(),
+            ..Default::default()
+        };
+
         if let Some(path) = path {
-            load_repository(path, &sender);
+            load_repository(path, load_options.clone(), &sender);
         }
 
+        let diff_type = match revision_range {
+            Some(_) => DiffType::Revision,
+            None => DiffType::Modified,
+        };
+
         let font_data = FontData::from_static(include_bytes!("fonts/JetBrainsMono-Regular.ttf"));
         let font_license = include_str!("fonts/OFL.txt").to_string();
 
         MyApp {
             app_data: None,
+            log: Log::default(),
+            file_log: None,
+            blame: None,
+            search: None,
             control_data: ControlData {
                 profiler,
                 font_license,
+                diff_type,
+                revision_from: revision_range
+                    .as_ref()
+                    .map(|r| r.from.clone())
+                    .unwrap_or_default(),
+                revision_to: revision_range
+                    .as_ref()
+                    .map(|r| r.to.clone())
+                    .unwrap_or_default(),
+                revision_merge_base: revision_range.as_ref().is_some_and(|r| r.merge_base),
+                load_options,
+                image_blend: 0.5,
                 ..Default::default()
             },
             sender,
@@ -79,7 +133,17 @@ impl MyApp {
         }
     }
 
-    fn update_app_data(&mut self, app_data: &AppData) {
+    fn update_app_data(&mut self, ctx: &Context, app_data: &AppData) {
+        if !app_data.errors.is_empty() {
+            self.control_data.show_error(app_data.errors.join("\n"));
+        }
+        // a range that can't be loaded anymore is dropped, unless another one was picked since
+        if app_data.revision_diff_data.is_none()
+            && self.control_data.load_options.revision_range == app_data.revision_range
+        {
+            self.control_data.load_options.revision_range = None;
+        }
+
         match self.control_data.diff_type {
             DiffType::Modified => {
                 if app_data.modified_diff_data.stats.files_changed == 0
@@ -95,6 +159,32 @@ impl MyApp {
                     self.control_data.diff_type = DiffType::Modified
                 }
             }
+            DiffType::Stash => {
+                if app_data.stash_diff.is_none() {
+                    self.control_data.load_options.stash = None;
+                    self.control_data.diff_type = DiffType::Modified;
+                }
+            }
+            DiffType::Revision | DiffType::Commit => (),
+        }
+
+        // the file might have changed
+        self.blame = None;
+        // and with it the lines the selection points at
+        self.control_data.selected_lines.clear();
+        ctx.memory_mut(|mem| mem.stop_text_input());
+
+        // the log survives refreshes as long as HEAD and the refs stay the same
+        if app_data.log_key != self.log.key {
+            let more = load_log(
+                PathBuf::from(app_data.project_path.clone()),
+                self.control_data.load_options.log_all_branches,
+                app_data.log_key.clone(),
+                &self.sender,
+            );
+            self.log = Log::new(app_data.log_key.clone(), more);
+            self.file_log = None;
+            self.search = None;
         }
 
         if self.watcher.is_none() {
@@ -106,19 +196,138 @@ impl MyApp {
         }
     }
 
+    /// Starts loading the history of the file selected for the log, if it changed.
+    fn update_file_log(&mut self) {
+        let loaded = self.file_log.as_ref().map(|l| &l.selection);
+        if self.control_data.file_log.as_ref() == loaded {
+            return;
+        }
+
+        self.file_log = match (&self.control_data.file_log, &self.app_data) {
+            (Some(selection), Some(app_data)) => Some(load_file_log(
+                PathBuf::from(app_data.project_path.clone()),
+                selection.clone(),
+                &self.sender,
+            )),
+            _ => None,
+        };
+    }
+
+    /// Restarts the log search whenever the search string changes.
+    fn update_search(&mut self) {
+        let text = self.control_data.search_string.trim();
+        if text.is_empty() {
+            self.search = None;
+            return;
+        }
+
+        let selection = SearchSelection {
+            text: text.to_owned(),
+            all_branches: self.control_data.load_options.log_all_branches,
+        };
+        if self.search.as_ref().map(|s| &s.selection) == Some(&selection) {
+            return;
+        }
+
+        if let Some(app_data) = &self.app_data {
+            self.search = Some(load_search(
+                PathBuf::from(app_data.project_path.clone()),
+                selection,
+                &self.sender,
+            ));
+        }
+    }
+
+    /// Creates the requested commit in the background, hooks can take a while.
+    fn update_commit(&mut self) {
+        let Some(app_data) = &self.app_data else {
+            return;
+        };
+        if let Some(request) = self.control_data.commit_request.take() {
+            self.control_data.committing = true;
+            create_commit(app_data.project_path.clone(), request, &self.sender);
+        }
+    }
+
+    /// Starts blaming the selected file, if the selection changed.
+    fn update_blame(&mut self) {
+        let selection = self.blame_selection();
+        if selection.as_ref() == self.blame.as_ref().map(|b| &b.selection) {
+            return;
+        }
+
+        self.blame = match (selection, &self.app_data) {
+            (Some(selection), Some(app_data)) => {
+                load_blame(
+                    PathBuf::from(app_data.project_path.clone()),
+                    selection.clone(),
+                    &self.sender,
+                );
+                Some(Blame {
+                    selection,
+                    lines: Vec::new(),
+                })
+            }
+            _ => None,
+        };
+    }
+
+    fn blame_selection(&self) -> Option<BlameSelection> {
+        let app_data = self.app_data.as_ref()?;
+        let diff = app_data
+            .diff_data(&self.control_data.diff_type)?
+            .diffs
+            .iter()
+            .find(|d| d.file_name() == self.control_data.selected_diff)?;
+
+        let load_options = &self.control_data.load_options;
+        let target = match self.control_data.diff_type {
+            DiffType::Modified => BlameTarget::WorkDir,
+            DiffType::Staged => BlameTarget::Index,
+            DiffType::Revision => {
+                BlameTarget::Revision(load_options.revision_range.as_ref()?.to.clone())
+            }
+            DiffType::Commit => BlameTarget::Revision(load_options.commit.as_ref()?.id.clone()),
+            DiffType::Stash => BlameTarget::Revision(load_options.stash.clone()?),
+        };
+
+        Some(BlameSelection {
+            file: diff.file_name(),
+            target,
+        })
+    }
+
     // only for messages that come from different threads
-    fn handle_messages(&mut self) {
+    fn handle_messages(&mut self, ctx: &Context) {
         match self.receiver.try_recv() {
             Ok(msg) => match msg {
                 Message::UpdateAppData(app_data) => {
-                    self.update_app_data(&app_data);
+                    self.update_app_data(ctx, &app_data);
                     self.app_data = Some(app_data);
                 }
+                Message::UpdateLog(page) => self.log.append(page),
+                Message::UpdateFileLog(page) => {
+                    if let Some(file_log) = &mut self.file_log {
+                        file_log.append(page);
+                    }
+                }
+                Message::UpdateSearch(page) => {
+                    if let Some(search) = &mut self.search {
+                        search.append(page);
+                    }
+                }
+                Message::UpdateBlame(selection, lines) => {
+                    if let Some(blame) = &mut self.blame {
+                        if blame.selection == selection {
+                            blame.lines = lines;
+                        }
+                    }
+                }
                 Message::UpdateWatcher(watcher) => self.watcher = Some(watcher),
-                Message::ShowError(error) => {
-                    self.control_data.error_information = error;
-                    self.control_data.show_err_dialog = true;
+                Message::FinishCommit(result) => {
+                    ui::message_area::finish(result, &mut self.control_data)
                 }
+                Message::ShowError(error) => self.control_data.show_error(error),
             },
             Err(err) => match err {
                 TryRecvError::Disconnected => panic!("Channel closed unexpectedly!"),
@@ -139,7 +348,11 @@ impl MyApp {
 
         if *should_refresh {
             if let Some(app_data) = &self.app_data {
-                load_repository(PathBuf::from(app_data.project_path.clone()), &self.sender);
+                load_repository(
+                    PathBuf::from(app_data.project_path.clone()),
+                    self.control_data.load_options.clone(),
+                    &self.sender,
+                );
             }
             *should_refresh = false;
         }
@@ -171,18 +384,41 @@ impl eframe::App for MyApp {
 
         ctx.set_fonts(fonts);
 
-        if let Some(app_data) = &mut self.app_data {
+        self.update_file_log();
+        self.update_blame();
+        self.update_search();
+        self.update_commit();
+
+        if let Some(app_data) = &self.app_data {
+            egui::SidePanel::right("stash panel")
+                .resizable(false)
+                .show_animated(ctx, self.control_data.stash_open, |ui| {
+                    ui::stash_area::ui(ui, app_data, &mut self.control_data);
+                });
+
+            egui::SidePanel::right("branch panel")
+                .resizable(false)
+                .show_animated(ctx, self.control_data.branch_open, |ui| {
+                    ui::branch_area::ui(ui, app_data, &mut self.control_data);
+                });
+
             egui::SidePanel::right("git log panel")
                 .resizable(false)
                 .show_animated(ctx, self.control_data.log_open, |ui| {
-                    ui::log::ui(ui, &app_data.commits, &mut self.control_data);
+                    ui::log::ui(
+                        ui,
+                        &mut self.log,
+                        self.file_log.as_ref(),
+                        self.search.as_ref(),
+                        &mut self.control_data,
+                    );
                 });
         }
 
         egui::CentralPanel::default().show(ctx, |ui| {
             ui::selection(ui, ctx, &mut self.control_data, &self.sender);
             if let Some(app_data) = &mut self.app_data {
-                ui::main(ui, app_data, &mut self.control_data);
+                ui::main(ui, app_data, self.blame.as_ref(), &mut self.control_data);
             }
         });
 
@@ -190,18 +426,170 @@ impl eframe::App for MyApp {
             self.control_data.profiler = puffin_egui::profiler_window(ctx);
         }
 
-        self.handle_messages();
+        self.handle_messages(ctx);
     }
 }
 
-fn load_repository(path: PathBuf, sender: &Sender<Message>) {
+fn load_repository(path: PathBuf, load_options: LoadOptions, sender: &Sender<Message>) {
     let s = sender.clone();
-    thread::spawn(move || match AppData::from_pathbuf(path) {
+    thread::spawn(move || match AppData::from_pathbuf(path, load_options) {
         Ok(app_data) => s
             .send(Message::UpdateAppData(app_data))
             .expect("Channel closed unexpectedly!"),
+        Err(AppDataCreationError::Commit) => s
+            .send(Message::ShowError("Error loading commit!".to_string()))
+            .expect("Channel closed unexpectedly!"),
+        Err(AppDataCreationError::Stash) => s
+            .send(Message::ShowError("Error loading stash!".to_string()))
+            .expect("Channel closed unexpectedly!"),
         Err(_) => s
             .send(Message::ShowError("Error loading diff!".to_string()))
             .expect("Channel closed unexpectedly!"),
     });
 }
+
+const LOG_PAGE_SIZE: usize = 500;
+// matching commits can be far apart, so they are sent more often
+const FILE_LOG_PAGE_SIZE: usize = 50;
+const SEARCH_BATCH_SIZE: usize = 200;
+
+/// Loads the first page of the log right away and every further page when something is sent
+/// to the returned sender. The thread ends once the sender is dropped.
+fn load_log(
+    path: PathBuf,
+    all_branches: bool,
+    key: String,
+    sender: &Sender<Message>,
+) -> Sender<()> {
+    let (more_sender, more_receiver) = mpsc::channel();
+    let s = sender.clone();
+    thread::spawn(move || {
+        let Some(path) = path.to_str().map(str::to_owned) else {
+            return;
+        };
+
+        let result = commit::walk_log(&path, all_branches, LOG_PAGE_SIZE, |commits, complete| {
+            let page = LogPage {
+                key: key.clone(),
+                commits,
+                complete,
+            };
+            s.send(Message::UpdateLog(page))
+                .expect("Channel closed unexpectedly!");
+            !complete && more_receiver.recv().is_ok()
+        });
+
+        if result.is_err() {
+            s.send(Message::ShowError("Error loading log!".to_string()))
+                .expect("Channel closed unexpectedly!");
+        }
+    });
+
+    more_sender
+}
+
+/// Streams the commits that changed a file until the returned `FileLog` is dropped.
+fn load_file_log(path: PathBuf, selection: FileLogSelection, sender: &Sender<Message>) -> FileLog {
+    let (running, stopped) = mpsc::channel();
+    let s = sender.clone();
+    let file_log = FileLog::new(selection.clone(), running);
+
+    thread::spawn(move || {
+        let Some(path) = path.to_str().map(str::to_owned) else {
+            return;
+        };
+
+        let result = commit::walk_file_log(
+            &path,
+            &selection.path,
+            selection.follow,
+            FILE_LOG_PAGE_SIZE,
+            || stopped.try_recv() != Err(TryRecvError::Disconnected),
+            |commits, complete| {
+                let page = FileLogPage {
+                    selection: selection.clone(),
+                    commits,
+                    complete,
+                };
+                s.send(Message::UpdateFileLog(page))
+                    .expect("Channel closed unexpectedly!");
+                true
+            },
+        );
+
+        if result.is_err() {
+            s.send(Message::ShowError(
+                "Error loading file history!".to_string(),
+            ))
+            .expect("Channel closed unexpectedly!");
+        }
+    });
+
+    file_log
+}
+
+fn create_commit(path: String, request: CommitRequest, sender: &Sender<Message>) {
+    let s = sender.clone();
+    thread::spawn(move || {
+        let result = commit::create(&path, &request.message, request.amend, request.run_hooks);
+        s.send(Message::FinishCommit(result))
+            .expect("Channel closed unexpectedly!");
+    });
+}
+
+/// Blames the file in the background, files that can't be blamed get no annotations.
+fn load_blame(path: PathBuf, selection: BlameSelection, sender: &Sender<Message>) {
+    let s = sender.clone();
+    thread::spawn(move || {
+        let Some(path) = path.to_str().map(str::to_owned) else {
+            return;
+        };
+
+        let lines = blame::get_blame(&path, &selection.file, &selection.target).unwrap_or_default();
+        s.send(Message::UpdateBlame(selection, lines))
+            .expect("Channel closed unexpectedly!");
+    });
+}
+
+/// Streams the commits matching the search until the returned `Search` is dropped.
+fn load_search(path: PathBuf, selection: SearchSelection, sender: &Sender<Message>) -> Search {
+    let (running, stopped) = mpsc::channel();
+    let query = Query::parse(&selection.text);
+    let search = Search::new(selection.clone(), query.clone(), running);
+    let Ok(query) = query else {
+        return search;
+    };
+
+    let s = sender.clone();
+    thread::spawn(move || {
+        let Some(path) = path.to_str().map(str::to_owned) else {
+            return;
+        };
+
+        let result = search::search_log(
+            &path,
+            selection.all_branches,
+            &query,
+            SEARCH_BATCH_SIZE,
+            |commits, complete| {
+                if !commits.is_empty() || complete {
+                    let page = SearchPage {
+                        selection: selection.clone(),
+                        commits,
+                        complete,
+                    };
+                    s.send(Message::UpdateSearch(page))
+                        .expect("Channel closed unexpectedly!");
+                }
+                stopped.try_recv() != Err(TryRecvError::Disconnected)
+            },
+        );
+
+        if result.is_err() {
+            s.send(Message::ShowError("Error searching log!".to_string()))
+                .expect("Channel closed unexpectedly!");
+        }
+    });
+
+    search
+}
@@ -0,0 +1,162 @@
+use chrono::{Local, NaiveDateTime};
+use egui::{Color32, FontId, Label, Rect, RichText, Sense, Ui};
+
+use crate::{
+    data::{CommitSelection, ControlData, DiffType},
+    git::{blame::BlameLine, text::DiffText, Diff},
+};
+
+const AUTHOR_WIDTH: usize = 12;
+const NEW: Color32 = Color32::from_rgb(230, 170, 60);
+const OLD: Color32 = Color32::from_rgb(110, 110, 110);
+
+/// Annotates the rows of the new side of a diff with the commit that last touched them,
+/// clicking an annotation opens that commit.
+pub fn ui(
+    ui: &mut Ui,
+    text: &DiffText,
+    diff: &Diff,
+    blame: &[Option<BlameLine>],
+    start: usize,
+    end: usize,
+    control_data: &mut ControlData,
+) {
+    puffin::profile_function!("blame::ui");
+
+    let end = std::cmp::min(end, text.rows);
+    ui.vertical(|ui| {
+        ui.spacing_mut().item_spacing.y = 0.0;
+        ui.add_space(3.0);
+
+        let mut previous = None;
+        for row in start..end {
+            let Some(line) = blame_line(text, diff, blame, row) else {
+                ui.label(RichText::new(" ").monospace());
+                previous = None;
+                continue;
+            };
+
+            // only the first row of a block from the same commit is annotated
+            let annotation = if previous == Some(&line.id) {
+                String::new()
+            } else {
+                format!(
+                    "{} {:<width$} {}",
+                    &line.id[..7],
+                    truncate(&line.author, AUTHOR_WIDTH),
+                    relative_date(line.time),
+                    width = AUTHOR_WIDTH
+                )
+            };
+            previous = Some(&line.id);
+
+            let response = ui
+                .add(
+                    Label::new(
+                        RichText::new(format!("{annotation:<32}"))
+                            .monospace()
+                            .color(age_color(line.time)),
+                    )
+                    .sense(Sense::click()),
+                )
+                .on_hover_ui(|ui| details(ui, line));
+            if response.clicked() {
+                open_commit(&line.id, control_data);
+            }
+        }
+    });
+}
+
+/// Shows the blame of the context line under the pointer, `rect` spans the rows from `start`.
+pub fn tooltip(
+    ui: &Ui,
+    rect: Rect,
+    text: &DiffText,
+    diff: &Diff,
+    blame: &[Option<BlameLine>],
+    start: usize,
+) {
+    let Some(pointer) = ui.ctx().pointer_hover_pos() else {
+        return;
+    };
+    if !ui.rect_contains_pointer(rect) {
+        return;
+    }
+
+    let row_height = ui.fonts(|f| f.row_height(&FontId::monospace(12.0)));
+    let row = start + ((pointer.y - rect.top() - 3.0).max(0.0) / row_height) as usize;
+    let is_context = text
+        .line_indices
+        .get(row)
+        .copied()
+        .flatten()
+        .is_some_and(|i| diff.lines[i].origin == ' ');
+    if !is_context {
+        return;
+    }
+
+    if let Some(line) = blame_line(text, diff, blame, row) {
+        egui::show_tooltip_at_pointer(ui.ctx(), ui.id().with("blame tooltip"), |ui| {
+            details(ui, line)
+        });
+    }
+}
+
+fn blame_line<'a>(
+    text: &DiffText,
+    diff: &Diff,
+    blame: &'a [Option<BlameLine>],
+    row: usize,
+) -> Option<&'a BlameLine> {
+    let index = text.line_indices.get(row).copied().flatten()?;
+    let new_lineno = diff.lines[index].new_lineno?;
+    blame.get(new_lineno as usize - 1)?.as_ref()
+}
+
+fn details(ui: &mut Ui, line: &BlameLine) {
+    ui.label(RichText::new(format!("commit {}", line.id)).color(Color32::LIGHT_BLUE));
+    ui.label(format!("{}, {}", line.author, line.time));
+    ui.label(RichText::new(&line.summary).color(Color32::WHITE));
+}
+
+fn open_commit(id: &str, control_data: &mut ControlData) {
+    control_data.load_options.commit = Some(CommitSelection {
+        id: id.to_owned(),
+        parent: 0,
+    });
+    control_data.diff_type = DiffType::Commit;
+    control_data.selected_lines.clear();
+    control_data.refresh();
+}
+
+fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() > width {
+        format!("{}…", text.chars().take(width - 1).collect::<String>())
+    } else {
+        text.to_owned()
+    }
+}
+
+fn relative_date(time: NaiveDateTime) -> String {
+    let days = (Local::now().naive_local() - time).num_days();
+    match days {
+        ..=0 => "today".to_owned(),
+        1 => "yesterday".to_owned(),
+        2..=13 => format!("{days} days ago"),
+        14..=60 => format!("{} weeks ago", days / 7),
+        61..=729 => format!("{} months ago", days / 30),
+        _ => format!("{} years ago", days / 365),
+    }
+}
+
+/// Fades from orange for today to gray for ten years and older.
+fn age_color(time: NaiveDateTime) -> Color32 {
+    let days = (Local::now().naive_local() - time).num_days().max(0) as f32;
+    let t = ((1.0 + days).ln() / (1.0 + 3650.0_f32).ln()).min(1.0);
+    let mix = |new: u8, old: u8| (new as f32 + (old as f32 - new as f32) * t) as u8;
+    Color32::from_rgb(
+        mix(NEW.r(), OLD.r()),
+        mix(NEW.g(), OLD.g()),
+        mix(NEW.b(), OLD.b()),
+    )
+}
@@ -0,0 +1,228 @@
+use egui::{Button, Color32, Label, RichText, ScrollArea, Sense, Ui};
+
+use crate::{
+    data::{AppData, ControlData},
+    git::branch::{self, Branch},
+};
+
+enum Action {
+    Checkout,
+    Rename(String),
+    Delete { force: bool },
+}
+
+pub fn ui(ui: &mut Ui, app_data: &AppData, control_data: &mut ControlData) {
+    puffin::profile_function!();
+    ui.add_space(10.0);
+
+    ui.horizontal(|ui| {
+        ui.heading("Branches");
+        if ui.button("Close").clicked() {
+            control_data.branch_open = false;
+        };
+    });
+
+    ui.horizontal(|ui| {
+        ui.label("New branch:");
+        ui.text_edit_singleline(&mut control_data.branch_name);
+    });
+    ui.horizontal(|ui| {
+        match &control_data.branch_start {
+            Some(id) => {
+                ui.label(format!("from {}", &id[..7]));
+                if ui
+                    .small_button("×")
+                    .on_hover_text("Start from HEAD")
+                    .clicked()
+                {
+                    control_data.branch_start = None;
+                }
+            }
+            None => {
+                ui.label("from HEAD")
+                    .on_hover_text("Use \"Branch\" on a commit in the log to start from there");
+            }
+        }
+        if ui
+            .add_enabled(
+                !control_data.branch_name.trim().is_empty(),
+                Button::new("Create"),
+            )
+            .clicked()
+        {
+            let result = branch::create(
+                &app_data.project_path,
+                control_data.branch_name.trim(),
+                control_data.branch_start.as_deref(),
+            );
+            if result.is_ok() {
+                control_data.branch_name.clear();
+                control_data.branch_start = None;
+            }
+            control_data.finish(result, "Error creating branch");
+        }
+    });
+    ui.separator();
+
+    let mut action = None;
+    ScrollArea::vertical()
+        .id_source("branch scroll area")
+        .show(ui, |ui| {
+            let (local, remote): (Vec<&Branch>, Vec<&Branch>) =
+                app_data.branches.iter().partition(|b| !b.remote);
+
+            for (title, branches) in [("Local", local), ("Remote", remote)] {
+                ui.label(RichText::new(title).strong());
+                if branches.is_empty() {
+                    ui.label(RichText::new("No branches").color(Color32::GRAY));
+                }
+                for branch in branches {
+                    if let Some(a) = show_branch(ui, branch, &app_data.project_path, control_data) {
+                        action = Some((a, branch));
+                    }
+                }
+                ui.separator();
+            }
+        });
+
+    let Some((action, branch)) = action else {
+        return;
+    };
+
+    let path = &app_data.project_path;
+    match action {
+        Action::Checkout => control_data.finish(
+            branch::checkout(path, &branch.name, branch.remote),
+            "Error checking out branch",
+        ),
+        Action::Rename(new_name) => control_data.finish(
+            branch::rename(path, &branch.name, new_name.trim()),
+            "Error renaming branch",
+        ),
+        Action::Delete { force } => control_data.finish(
+            branch::delete(path, &branch.name, force),
+            "Error deleting branch",
+        ),
+    }
+}
+
+/// Shows the checked out branch next to the project path.
+pub fn header(ui: &mut Ui, head: &str, control_data: &mut ControlData) {
+    if ui
+        .add(
+            Label::new(
+                RichText::new(head)
+                    .heading()
+                    .color(Color32::from_rgb(80, 180, 80)),
+            )
+            .sense(Sense::click()),
+        )
+        .on_hover_text("Click to show all branches")
+        .clicked()
+    {
+        control_data.branch_open = !control_data.branch_open;
+    }
+}
+
+fn show_branch(
+    ui: &mut Ui,
+    branch: &Branch,
+    project_path: &String,
+    control_data: &mut ControlData,
+) -> Option<Action> {
+    let mut action = None;
+
+    ui.horizontal(|ui| {
+        let text = if branch.head {
+            RichText::new(&branch.name).color(Color32::WHITE).strong()
+        } else {
+            RichText::new(&branch.name).color(Color32::LIGHT_BLUE)
+        };
+        let response = ui
+            .add(Label::new(text).sense(Sense::click()))
+            .on_hover_text("Double click to checkout");
+        if response.double_clicked() && !branch.head {
+            action = Some(Action::Checkout);
+        }
+
+        if branch.ahead > 0 {
+            ui.label(RichText::new(format!("↑{}", branch.ahead)).color(Color32::GREEN))
+                .on_hover_text("Commits not pushed to the upstream");
+        }
+        if branch.behind > 0 {
+            ui.label(RichText::new(format!("↓{}", branch.behind)).color(Color32::YELLOW))
+                .on_hover_text("Commits of the upstream not in the branch");
+        }
+    });
+    if let Some(upstream) = &branch.upstream {
+        ui.label(RichText::new(format!("tracks {upstream}")).color(Color32::GRAY));
+    }
+
+    if let Some((name, new_name)) = &mut control_data.branch_rename {
+        if *name == branch.name {
+            let mut done = false;
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(new_name);
+                if ui.small_button("Rename").clicked() && !new_name.trim().is_empty() {
+                    action = Some(Action::Rename(new_name.clone()));
+                    done = true;
+                }
+                if ui.small_button("Cancel").clicked() {
+                    done = true;
+                }
+            });
+            if done {
+                control_data.branch_rename = None;
+            }
+            ui.separator();
+            return action;
+        }
+    }
+
+    ui.horizontal(|ui| {
+        if !branch.head && ui.small_button("Checkout").clicked() {
+            action = Some(Action::Checkout);
+        }
+        if branch.remote {
+            return;
+        }
+        if ui.small_button("Rename").clicked() {
+            control_data.branch_rename = Some((branch.name.clone(), branch.name.clone()));
+        }
+        if branch.head {
+            return;
+        }
+
+        // deleting can't be undone, so it takes a second click and a warning for lost commits
+        match &control_data.branch_to_delete {
+            Some((name, merged)) if *name == branch.name => {
+                let merged = *merged;
+                let response = if merged {
+                    ui.small_button(RichText::new("Really delete?").color(Color32::RED))
+                } else {
+                    ui.small_button(RichText::new("Delete unmerged commits?").color(Color32::RED))
+                        .on_hover_text(
+                            "Neither HEAD nor the upstream contain all commits of the branch",
+                        )
+                };
+                if response.clicked() {
+                    action = Some(Action::Delete { force: !merged });
+                    control_data.branch_to_delete = None;
+                }
+                if ui.small_button("Cancel").clicked() {
+                    control_data.branch_to_delete = None;
+                }
+            }
+            _ => {
+                if ui.small_button("Delete").clicked() {
+                    // the warning errs on the safe side when the check fails
+                    let merged = branch::merged(project_path, &branch.name).unwrap_or(false);
+                    control_data.branch_to_delete = Some((branch.name.clone(), merged));
+                }
+            }
+        }
+    });
+    ui.separator();
+
+    action
+}
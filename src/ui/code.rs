@@ -1,98 +1,166 @@
+use std::{ops::Range, path::Path};
+
 use egui::{
-    text::LayoutJob,
+    text::{CCursor, CCursorRange, LayoutJob},
+    text_edit::TextEditState,
     util::cache::{ComputerMut, FrameCache},
-    Color32, Context, FontFamily, FontId, Layout, Response, TextEdit, TextFormat, Ui,
+    Color32, Context, FontFamily, FontId, Id, Layout, TextEdit, TextFormat, Ui,
+};
+
+use crate::{
+    git::text::DiffText,
+    ui::syntax::{self, SyntaxRows},
 };
 
-use crate::git::Diff;
+const INSERTION_BACKGROUND: Color32 = Color32::from_rgb(25, 65, 25);
+const DELETION_BACKGROUND: Color32 = Color32::from_rgb(75, 25, 25);
+const INSERTION_EMPHASIS: Color32 = Color32::from_rgb(40, 120, 40);
+const DELETION_EMPHASIS: Color32 = Color32::from_rgb(140, 40, 40);
+const MOVED_INSERTION_BACKGROUND: Color32 = Color32::from_rgb(25, 55, 75);
+const MOVED_DELETION_BACKGROUND: Color32 = Color32::from_rgb(65, 30, 70);
+const WHITESPACE: Color32 = Color32::from_rgb(100, 100, 100);
+
+/// What the code did with the text selection this frame.
+pub enum Selection {
+    Unchanged,
+    /// A new selection was made, touching this range of rows.
+    Rows(Range<usize>),
+    /// The code lost focus, taking the selection with it.
+    Cleared,
+}
 
-pub fn ui(ui: &mut Ui, diff: &Diff, start: usize, end: usize) -> Response {
+pub fn ui(
+    ui: &mut Ui,
+    text: &DiffText,
+    file_name: &Path,
+    start: usize,
+    end: usize,
+    show_whitespace: bool,
+) -> Selection {
     puffin::profile_function!("code::ui");
 
+    let syntax_rows = syntax::highlight(ui.ctx(), text, file_name);
+
     let mut layouter = |ui: &egui::Ui, string: &str, _wrap_width: f32| {
-        let layout_job: egui::text::LayoutJob = highlight(
-            ui.ctx(),
-            string,
-            start,
-            &diff.header_indices,
-            &diff.insertion_indices,
-            &diff.deletion_indices,
-            &diff.neutral_indices,
-        );
+        let layout_job: egui::text::LayoutJob =
+            highlight(ui.ctx(), string, start, show_whitespace, text, &syntax_rows);
         ui.fonts(|f| f.layout_job(layout_job))
     };
 
-    let lines = diff.content.lines().collect::<Vec<&str>>();
+    // split at \n only, so carriage returns stay part of their row
+    let lines = text.content.split_terminator('\n').collect::<Vec<&str>>();
     let end = std::cmp::min(end, lines.len());
     let content = &lines[start..end].join("\n");
 
-    ui.with_layout(Layout::left_to_right(egui::Align::Min), |ui| {
-        ui.add(
-            TextEdit::multiline(&mut content.as_str())
+    let (id, before, output) = ui
+        .with_layout(Layout::left_to_right(egui::Align::Min), |ui| {
+            // the id is needed before showing the text edit, to put its selection back
+            let id = ui.next_auto_id();
+            let before = follow_rows(ui.ctx(), id, &lines[start..end], start);
+            let output = TextEdit::multiline(&mut content.as_str())
+                .id(id)
                 .desired_width(f32::INFINITY)
                 .frame(false)
                 .code_editor()
-                .layouter(&mut layouter),
-        );
-    })
-    .response
+                .layouter(&mut layouter)
+                .show(ui);
+            (id, before, output)
+        })
+        .inner;
+
+    if output.response.lost_focus() {
+        ui.data_mut(|data| data.remove::<[(usize, usize); 2]>(id));
+        return Selection::Cleared;
+    }
+    let Some(range) = output
+        .cursor_range
+        .map(|range| range.as_ccursor_range())
+        .filter(|&range| Some(range) != before)
+    else {
+        return Selection::Unchanged;
+    };
+
+    let [first, last] = [range.primary, range.secondary]
+        .map(|cursor| position(&lines[start..end], start, cursor.index));
+    ui.data_mut(|data| data.insert_temp(id, [first, last]));
+    Selection::Rows(first.0.min(last.0)..first.0.max(last.0) + 1)
+}
+
+// the text edit only holds the visible rows, so its selection is put back where it was made
+// when they scroll, rows scrolled out of view are cut off
+fn follow_rows(ctx: &Context, id: Id, visible: &[&str], start: usize) -> Option<CCursorRange> {
+    let mut state = TextEditState::load(ctx, id)?;
+    let Some(made) = ctx.data(|data| data.get_temp::<[(usize, usize); 2]>(id)) else {
+        return state.cursor.char_range();
+    };
+
+    let [primary, secondary] =
+        made.map(|(row, column)| CCursor::new(index(visible, start, row, column)));
+    let range = CCursorRange { primary, secondary };
+    if state.cursor.char_range() != Some(range) {
+        state.cursor.set_char_range(Some(range));
+        state.store(ctx, id);
+    }
+    Some(range)
+}
+
+/// Row and column of a character of the visible rows.
+fn position(visible: &[&str], start: usize, index: usize) -> (usize, usize) {
+    let mut rest = index;
+    for (row, line) in visible.iter().enumerate() {
+        let chars = line.chars().count();
+        if rest <= chars {
+            return (start + row, rest);
+        }
+        rest -= chars + 1;
+    }
+    let last = visible.last().map_or(0, |line| line.chars().count());
+    (start + visible.len().saturating_sub(1), last)
+}
+
+/// Character at a row and column, clamped to the visible rows.
+fn index(visible: &[&str], start: usize, row: usize, column: usize) -> usize {
+    let chars = |lines: &[&str]| -> usize { lines.iter().map(|l| l.chars().count() + 1).sum() };
+    match row.checked_sub(start) {
+        None => 0,
+        Some(row) if row >= visible.len() => chars(visible).saturating_sub(1),
+        Some(row) => chars(&visible[..row]) + column.min(visible[row].chars().count()),
+    }
 }
 
 type HighlightCache = FrameCache<LayoutJob, LayoutHandler>;
 
 fn highlight(
     ctx: &Context,
-    text: &str,
+    string: &str,
     offset: usize,
-    header_indices: &Vec<usize>,
-    insertion_indices: &Vec<usize>,
-    deletion_indices: &Vec<usize>,
-    neutral_indices: &Vec<usize>,
+    show_whitespace: bool,
+    text: &DiffText,
+    syntax_rows: &SyntaxRows,
 ) -> LayoutJob {
-    impl
-        ComputerMut<
-            (
-                &str,
-                usize,
-                &Vec<usize>,
-                &Vec<usize>,
-                &Vec<usize>,
-                &Vec<usize>,
-            ),
-            LayoutJob,
-        > for LayoutHandler
-    {
+    impl ComputerMut<(&str, usize, bool, &DiffText, &SyntaxRows), LayoutJob> for LayoutHandler {
         fn compute(
             &mut self,
-            (text, offset, header_indices, insertion_indices, deletion_indices, neutral_indices): (
+            (string, offset, show_whitespace, text, syntax_rows): (
                 &str,
                 usize,
-                &Vec<usize>,
-                &Vec<usize>,
-                &Vec<usize>,
-                &Vec<usize>,
+                bool,
+                &DiffText,
+                &SyntaxRows,
             ),
         ) -> LayoutJob {
             puffin::profile_function!();
-            LayoutHandler::layout_job(
-                text,
-                offset,
-                header_indices,
-                insertion_indices,
-                deletion_indices,
-                neutral_indices,
-            )
+            LayoutHandler::layout_job(string, offset, show_whitespace, text, syntax_rows)
         }
     }
 
     ctx.memory_mut(|mem| {
         mem.caches.cache::<HighlightCache>().get((
-            text,
+            string,
             offset,
-            header_indices,
-            insertion_indices,
-            deletion_indices,
-            neutral_indices,
+            show_whitespace,
+            text,
+            syntax_rows,
         ))
     })
 }
@@ -102,12 +170,11 @@ struct LayoutHandler {}
 
 impl LayoutHandler {
     fn layout_job(
-        text: &str,
+        string: &str,
         offset: usize,
-        header_indices: &[usize],
-        insertion_indices: &[usize],
-        deletion_indices: &[usize],
-        neutral_indices: &[usize],
+        show_whitespace: bool,
+        text: &DiffText,
+        syntax_rows: &SyntaxRows,
     ) -> LayoutJob {
         puffin::profile_function!();
 
@@ -118,33 +185,145 @@ impl LayoutHandler {
             FontId::new(12.0, FontFamily::Monospace),
             Color32::from_rgb(7, 138, 171),
         );
-        let insertion_format =
-            TextFormat::simple(FontId::new(12.0, FontFamily::Monospace), Color32::GREEN);
-        let deletion_format =
-            TextFormat::simple(FontId::new(12.0, FontFamily::Monospace), Color32::RED);
         let neutral_format =
             TextFormat::simple(FontId::new(12.0, FontFamily::Monospace), Color32::WHITE);
 
-        for (i, line) in text.lines().enumerate() {
-            if header_indices.contains(&(i + offset)) {
+        for (i, line) in string.split('\n').enumerate() {
+            let row = i + offset;
+            if text.header_indices.contains(&row) {
                 let green_part = line.split(' ').take(4).collect::<Vec<&str>>().join(" ");
                 let white_part = line.split(' ').skip(4).collect::<Vec<&str>>().join(" ");
                 job.append(&green_part, 0.0, header_format.clone());
                 job.append(" ", 0.0, neutral_format.clone());
                 job.append(&white_part, 0.0, neutral_format.clone());
                 job.append("\n", 0.0, neutral_format.clone());
+                continue;
+            }
+
+            let mut format = neutral_format.clone();
+            let mut emphasis_background = format.background;
+            if text.insertion_indices.contains(&row) {
+                format.background = INSERTION_BACKGROUND;
+                emphasis_background = INSERTION_EMPHASIS;
             }
-            if insertion_indices.contains(&(i + offset)) {
-                job.append(format!("{line}\n").as_str(), 0.0, insertion_format.clone());
+            if text.deletion_indices.contains(&row) {
+                format.background = DELETION_BACKGROUND;
+                emphasis_background = DELETION_EMPHASIS;
             }
-            if deletion_indices.contains(&(i + offset)) {
-                job.append(format!("{line}\n").as_str(), 0.0, deletion_format.clone());
+            if text.moved_indices.contains(&row) {
+                format.background = if text.insertion_indices.contains(&row) {
+                    MOVED_INSERTION_BACKGROUND
+                } else {
+                    MOVED_DELETION_BACKGROUND
+                };
             }
-            if neutral_indices.contains(&(i + offset)) {
-                job.append(format!("{line}\n").as_str(), 0.0, neutral_format.clone());
+
+            let (line, carriage_return) = match line.strip_suffix('\r') {
+                Some(line) => (line, true),
+                None => (line, false),
+            };
+            let trailing = line.trim_end_matches([' ', '\t']).len();
+
+            let emphasis = text.emphasis.get(row).map(Vec::as_slice).unwrap_or(&[]);
+            let tokens = syntax_rows
+                .rows
+                .get(row)
+                .filter(|tokens| tokens.last().is_some_and(|(r, _)| r.end == line.len()))
+                .cloned()
+                .unwrap_or_else(|| vec![(0..line.len(), format.color)]);
+
+            for (range, color) in tokens {
+                for (range, emphasized) in split_at_emphasis(range, emphasis) {
+                    let background = if emphasized {
+                        emphasis_background
+                    } else {
+                        format.background
+                    };
+                    let token_format = TextFormat {
+                        color,
+                        background,
+                        ..format.clone()
+                    };
+                    if show_whitespace {
+                        append_whitespace(&mut job, line, range, trailing, token_format);
+                    } else {
+                        job.append(&line[range], 0.0, token_format);
+                    }
+                }
             }
+
+            // every character of the row needs a glyph, otherwise the selection gets shifted
+            if carriage_return {
+                if show_whitespace {
+                    job.append("␍", 0.0, whitespace_format(&format));
+                } else {
+                    job.append(" ", 0.0, format.clone());
+                }
+            }
+            job.append("\n", 0.0, format);
         }
 
         job
     }
 }
+
+/// Appends the token with tabs and trailing spaces replaced by markers, one per character.
+fn append_whitespace(
+    job: &mut LayoutJob,
+    line: &str,
+    range: Range<usize>,
+    trailing: usize,
+    format: TextFormat,
+) {
+    let mut start = range.start;
+    for (i, c) in line[range.clone()].char_indices() {
+        let i = i + range.start;
+        let marker = match c {
+            '\t' => "→",
+            ' ' if i >= trailing => "·",
+            _ => continue,
+        };
+
+        if start < i {
+            job.append(&line[start..i], 0.0, format.clone());
+        }
+        job.append(marker, 0.0, whitespace_format(&format));
+        start = i + 1;
+    }
+
+    if start < range.end {
+        job.append(&line[start..range.end], 0.0, format);
+    }
+}
+
+fn whitespace_format(format: &TextFormat) -> TextFormat {
+    TextFormat {
+        color: WHITESPACE,
+        ..format.clone()
+    }
+}
+
+/// Splits a token at the borders of the emphasized ranges of its row.
+fn split_at_emphasis(range: Range<usize>, emphasis: &[Range<usize>]) -> Vec<(Range<usize>, bool)> {
+    let mut parts = Vec::new();
+    let mut start = range.start;
+
+    for span in emphasis {
+        if span.end <= start || span.start >= range.end {
+            continue;
+        }
+
+        if span.start > start {
+            parts.push((start..span.start, false));
+        }
+        let end = std::cmp::min(span.end, range.end);
+        parts.push((std::cmp::max(span.start, start)..end, true));
+        start = end;
+    }
+
+    if start < range.end {
+        parts.push((start..range.end, false));
+    }
+
+    parts
+}
@@ -0,0 +1,48 @@
+use egui::{Color32, RichText, Ui};
+
+use crate::{data::ControlData, git::commit::Commit};
+
+pub fn ui(ui: &mut Ui, commit: &Commit, control_data: &mut ControlData) {
+    puffin::profile_function!();
+
+    ui.add_space(10.0);
+    ui.label(RichText::new(format!("commit {}", commit.id)).color(Color32::LIGHT_BLUE));
+    ui.label(
+        RichText::new(format!(
+            "Author: {} <{}>",
+            commit.author.name, commit.author.email
+        ))
+        .color(Color32::WHITE),
+    );
+    ui.label(RichText::new(format!("Date: {}", commit.time)).color(Color32::WHITE));
+
+    if commit.parents.len() > 1 {
+        ui.horizontal(|ui| {
+            ui.label("Parent:");
+            let Some(selection) = &mut control_data.load_options.commit else {
+                return;
+            };
+
+            let mut selected_parent = selection.parent;
+            for (i, parent) in commit.parents.iter().enumerate() {
+                ui.selectable_value(
+                    &mut selected_parent,
+                    i,
+                    format!("{} ({})", i + 1, &parent[..7]),
+                )
+                .on_hover_text(parent);
+            }
+
+            if selected_parent != selection.parent {
+                selection.parent = selected_parent;
+                control_data.refresh();
+            }
+        });
+    }
+
+    ui.add_space(10.0);
+    ui.horizontal(|ui| {
+        ui.add_space(10.0);
+        ui.label(RichText::new(commit.message.trim_end()).color(Color32::WHITE));
+    });
+}
@@ -0,0 +1,96 @@
+use egui::{Color32, RichText, ScrollArea, Ui};
+
+use crate::{
+    data::ControlData,
+    git::{
+        conflict::{self, ConflictFile, Region, Resolution},
+        stage,
+    },
+};
+
+pub fn ui(
+    ui: &mut Ui,
+    conflict: &ConflictFile,
+    project_path: &String,
+    control_data: &mut ControlData,
+) {
+    puffin::profile_function!();
+
+    ui.horizontal(|ui| {
+        let hover_text = if conflict.regions.is_empty() {
+            "Stage the file"
+        } else {
+            "Stage the file, even though it still contains conflict markers"
+        };
+        if ui
+            .button("Mark resolved")
+            .on_hover_text(hover_text)
+            .clicked()
+        {
+            control_data.finish(
+                stage::stage_file(project_path, &conflict.path),
+                "Error resolving conflict",
+            );
+        }
+        ui.label(
+            RichText::new(format!("{} conflicts left", conflict.regions.len()))
+                .color(Color32::GRAY),
+        );
+    });
+
+    for (i, region) in conflict.regions.iter().enumerate() {
+        ui.horizontal(|ui| {
+            ui.label(format!("Conflict {}", i + 1))
+                .on_hover_ui(|ui| preview(ui, region));
+            for (label, resolution) in [
+                ("Take ours", Resolution::Ours),
+                ("Take theirs", Resolution::Theirs),
+                ("Take both", Resolution::Both),
+            ] {
+                if ui.small_button(label).clicked() {
+                    let result = conflict::resolve(project_path, &conflict.path, i, resolution);
+                    control_data.finish(result, "Error resolving conflict");
+                }
+            }
+        });
+    }
+    ui.separator();
+
+    ui.columns(3, |columns| {
+        pane(&mut columns[0], "Base", &conflict.base);
+        pane(&mut columns[1], "Ours", &conflict.ours);
+        pane(&mut columns[2], "Theirs", &conflict.theirs);
+    });
+}
+
+fn pane(ui: &mut Ui, title: &str, content: &str) {
+    ui.label(RichText::new(title).strong());
+    ScrollArea::both()
+        .id_source(title)
+        .auto_shrink([false, false])
+        .show(ui, |ui| {
+            ui.label(RichText::new(content).monospace().color(Color32::WHITE));
+        });
+}
+
+fn preview(ui: &mut Ui, region: &Region) {
+    ui.label(
+        RichText::new(region.ours.concat())
+            .monospace()
+            .color(Color32::GREEN),
+    );
+    if !region.base.is_empty() {
+        ui.separator();
+        ui.label(
+            RichText::new(region.base.concat())
+                .monospace()
+                .color(Color32::GRAY),
+        );
+    }
+    ui.separator();
+    ui.label(
+        RichText::new(region.theirs.concat())
+            .monospace()
+            .color(Color32::LIGHT_BLUE),
+    );
+}
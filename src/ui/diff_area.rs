@@ -3,29 +3,183 @@ use std::ops::Range;
 use egui::{Color32, RichText, ScrollArea, Ui};
 
 use crate::{
-    git::Diff,
-    ui::{code, line_numbers, origins},
+    data::{Blame, ControlData, DiffType},
+    git::{blame::BlameLine, text::DiffText, Diff},
+    ui::{
+        blame,
+        code::{self, Selection},
+        expand, line_numbers, moved, origins, stage_area,
+    },
 };
 
-pub fn ui(ui: &mut Ui, diff: &Diff) {
+const ROW_HEIGHT: f32 = 10.0;
+
+/// Returns the indices of the diff lines selected in the code when the selection changed, they
+/// are empty when it was cleared.
+pub fn ui(
+    ui: &mut Ui,
+    diff: &Diff,
+    blame: Option<&Blame>,
+    project_path: &String,
+    control_data: &mut ControlData,
+) -> Option<Vec<usize>> {
     puffin::profile_function!();
 
     if diff.lines.is_empty() {
         ui.label(RichText::new("No content").color(Color32::GRAY));
-        return;
+        return None;
     }
 
-    let total_rows = diff.lines.len() + diff.headers.len();
+    let blame = blame.map(|b| b.lines.as_slice()).unwrap_or_default();
+    if control_data.split_view {
+        show_split(ui, diff, blame, project_path, control_data)
+    } else {
+        show_unified(ui, diff, blame, project_path, control_data)
+    }
+}
 
-    ScrollArea::both()
+fn show_unified(
+    ui: &mut Ui,
+    diff: &Diff,
+    blame: &[Option<BlameLine>],
+    project_path: &String,
+    control_data: &mut ControlData,
+) -> Option<Vec<usize>> {
+    let mut area = ScrollArea::both()
         .id_source("diff area")
-        .auto_shrink([false, false])
-        .show_rows(ui, 10.0, total_rows, |ui, row_range| {
-            let Range { start, end } = row_range;
-            ui.horizontal(|ui| {
-                line_numbers::ui(ui, diff, start, end);
-                origins::ui(ui, diff, start, end);
-                code::ui(ui, diff, start, end);
-            });
-        });
+        .auto_shrink([false, false]);
+    if let Some(offset) = scroll_offset(ui, &[&diff.unified], control_data) {
+        area = area.vertical_scroll_offset(offset);
+    }
+    area.show_rows(ui, ROW_HEIGHT, diff.unified.rows, |ui, row_range| {
+        let Range { start, end } = row_range;
+        ui.horizontal(|ui| {
+            gutter(
+                ui,
+                &diff.unified,
+                diff,
+                project_path,
+                start,
+                end,
+                control_data,
+            );
+            show_text(ui, &diff.unified, diff, blame, start, end, control_data)
+        })
+        .inner
+    })
+    .inner
+}
+
+// both sides live in the same vertical scroll area, so they always scroll in lockstep
+fn show_split(
+    ui: &mut Ui,
+    diff: &Diff,
+    blame: &[Option<BlameLine>],
+    project_path: &String,
+    control_data: &mut ControlData,
+) -> Option<Vec<usize>> {
+    let total_rows = diff.left.rows.max(diff.right.rows);
+
+    let mut area = ScrollArea::vertical()
+        .id_source("split diff area")
+        .auto_shrink([false, false]);
+    if let Some(offset) = scroll_offset(ui, &[&diff.left, &diff.right], control_data) {
+        area = area.vertical_scroll_offset(offset);
+    }
+    area.show_rows(ui, ROW_HEIGHT, total_rows, |ui, row_range| {
+        let Range { start, end } = row_range;
+        ui.columns(2, |columns| {
+            let left = ScrollArea::horizontal()
+                .id_source("split diff area left")
+                .show(&mut columns[0], |ui| {
+                    // the blame belongs to the new side only
+                    ui.horizontal(|ui| {
+                        gutter(ui, &diff.left, diff, project_path, start, end, control_data);
+                        show_text(ui, &diff.left, diff, &[], start, end, control_data)
+                    })
+                    .inner
+                })
+                .inner;
+            let right = ScrollArea::horizontal()
+                .id_source("split diff area right")
+                .show(&mut columns[1], |ui| {
+                    ui.horizontal(|ui| {
+                        show_text(ui, &diff.right, diff, blame, start, end, control_data)
+                    })
+                    .inner
+                })
+                .inner;
+            // focus moving to the other side clears one and selects in the other
+            left.into_iter()
+                .chain(right)
+                .max_by_key(|lines| !lines.is_empty())
+        })
+    })
+    .inner
+}
+
+/// Buttons acting on whole hunks, shown next to their header rows.
+fn gutter(
+    ui: &mut Ui,
+    text: &DiffText,
+    diff: &Diff,
+    project_path: &String,
+    start: usize,
+    end: usize,
+    control_data: &mut ControlData,
+) {
+    if matches!(
+        control_data.diff_type,
+        DiffType::Modified | DiffType::Staged
+    ) {
+        stage_area::hunks(ui, text, diff, project_path, start, end, control_data);
+    }
+    expand::ui(ui, text, diff, start, end, control_data);
+}
+
+fn show_text(
+    ui: &mut Ui,
+    text: &DiffText,
+    diff: &Diff,
+    blame: &[Option<BlameLine>],
+    start: usize,
+    end: usize,
+    control_data: &mut ControlData,
+) -> Option<Vec<usize>> {
+    if !diff.moves.is_empty() {
+        moved::ui(ui, text, diff, start, end, control_data);
+    }
+    if control_data.show_blame && !blame.is_empty() {
+        blame::ui(ui, text, diff, blame, start, end, control_data);
+    }
+    line_numbers::ui(ui, text, start, end);
+    origins::ui(ui, text, start, end);
+    let selection = code::ui(
+        ui,
+        text,
+        &diff.file_name(),
+        start,
+        end,
+        control_data.show_whitespace,
+    );
+    if !control_data.show_blame {
+        blame::tooltip(ui, ui.min_rect(), text, diff, blame, start);
+    }
+    match selection {
+        Selection::Unchanged => None,
+        Selection::Rows(rows) => Some(
+            rows.filter_map(|row| text.line_indices.get(row).copied().flatten())
+                .collect(),
+        ),
+        Selection::Cleared => Some(Vec::new()),
+    }
+}
+
+/// Offset of the row showing `scroll_to_line` in one of the texts, the request is used up.
+fn scroll_offset(ui: &Ui, texts: &[&DiffText], control_data: &mut ControlData) -> Option<f32> {
+    let line = control_data.scroll_to_line.take()?;
+    let row = texts
+        .iter()
+        .find_map(|text| text.line_indices.iter().position(|&i| i == Some(line)))?;
+    Some(row as f32 * (ROW_HEIGHT + ui.spacing().item_spacing.y))
 }
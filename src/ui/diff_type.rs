@@ -21,8 +21,49 @@ pub fn ui(ui: &mut Ui, control_data: &mut ControlData) {
                     DiffType::Staged.label_text(),
                 )
                 .clicked()
+            || ui
+                .selectable_value(
+                    &mut selected_diff_type,
+                    DiffType::Revision,
+                    DiffType::Revision.label_text(),
+                )
+                .clicked()
         {
-            control_data.diff_type = selected_diff_type;
+            select(control_data, selected_diff_type.clone());
+        }
+
+        if control_data.load_options.commit.is_some()
+            && ui
+                .selectable_value(
+                    &mut selected_diff_type,
+                    DiffType::Commit,
+                    DiffType::Commit.label_text(),
+                )
+                .clicked()
+        {
+            select(control_data, selected_diff_type.clone());
+        }
+
+        if control_data.load_options.stash.is_some()
+            && ui
+                .selectable_value(
+                    &mut selected_diff_type,
+                    DiffType::Stash,
+                    DiffType::Stash.label_text(),
+                )
+                .clicked()
+        {
+            select(control_data, selected_diff_type);
         }
     });
 }
+
+fn select(control_data: &mut ControlData, diff_type: DiffType) {
+    // lines revealed in one diff shouldn't show up in another
+    let settings = &mut control_data.load_options.diff_settings;
+    if diff_type != control_data.diff_type && !settings.expanded.is_empty() {
+        settings.expanded.clear();
+        control_data.refresh();
+    }
+    control_data.diff_type = diff_type;
+}
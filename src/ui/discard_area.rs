@@ -0,0 +1,83 @@
+use anyhow::Result;
+use egui::{Color32, Context, RichText, ScrollArea, Window};
+
+use crate::{
+    data::ControlData,
+    git::discard::{self, Discarded},
+};
+
+pub fn window(ctx: &Context, project_path: &String, control_data: &mut ControlData) {
+    puffin::profile_function!();
+
+    let mut open = control_data.show_discarded;
+    let mut restored = None;
+    let mut forgotten = None;
+
+    Window::new("Discarded changes")
+        .collapsible(false)
+        .open(&mut open)
+        .show(ctx, |ui| {
+            ui.checkbox(
+                &mut control_data.save_discarded,
+                "Also keep discarded changes in .git/contrast/discarded",
+            );
+            ui.separator();
+
+            ScrollArea::vertical().show(ui, |ui| {
+                for (i, discarded) in control_data.discarded.iter().enumerate().rev() {
+                    ui.horizontal(|ui| {
+                        if ui.button("Restore").clicked() {
+                            restored = Some(i);
+                        }
+                        if ui.button("Forget").clicked() {
+                            forgotten = Some(i);
+                        }
+                        ui.label(
+                            RichText::new(discarded.file.to_str().unwrap_or_default())
+                                .color(Color32::WHITE),
+                        );
+                        ui.label(RichText::new(discarded.time.to_string()).color(Color32::GRAY));
+                    })
+                    .response
+                    .on_hover_text(
+                        RichText::new(String::from_utf8_lossy(&discarded.patch)).monospace(),
+                    );
+                }
+            });
+        });
+
+    if let Some(i) = restored {
+        let result = discard::restore(project_path, &control_data.discarded[i]);
+        if result.is_ok() {
+            control_data.discarded.remove(i);
+        }
+        control_data.finish(result, "Error restoring changes");
+    }
+
+    if let Some(i) = forgotten {
+        control_data.discarded.remove(i);
+    }
+
+    control_data.show_discarded = open && !control_data.discarded.is_empty();
+}
+
+/// Records the discarded changes in the undo history.
+pub fn finish(result: Result<Discarded>, project_path: &String, control_data: &mut ControlData) {
+    let discarded = match result {
+        Ok(discarded) => discarded,
+        Err(e) => {
+            control_data.show_error(format!("Error discarding changes: {e}"));
+            return;
+        }
+    };
+
+    if control_data.save_discarded {
+        if let Err(e) = discard::save(project_path, &discarded) {
+            control_data.show_error(format!("Error saving discarded changes: {e}"));
+        }
+    }
+
+    control_data.discarded.push(discarded);
+    control_data.selected_lines.clear();
+    control_data.refresh();
+}
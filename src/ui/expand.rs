@@ -0,0 +1,91 @@
+use std::ops::Range;
+
+use egui::{Color32, Label, RichText, Sense, Ui};
+
+use crate::{
+    data::ControlData,
+    git::{context::new_start, text::DiffText, Diff},
+};
+
+/// Lines revealed by one click on ↑ or ↓.
+const STEP: u32 = 10;
+const COLOR: Color32 = Color32::from_rgb(7, 138, 171);
+
+/// Buttons on the header rows that reveal the hidden lines between a hunk and the one before.
+pub fn ui(
+    ui: &mut Ui,
+    text: &DiffText,
+    diff: &Diff,
+    start: usize,
+    end: usize,
+    control_data: &mut ControlData,
+) {
+    puffin::profile_function!("expand::ui");
+
+    let end = std::cmp::min(end, text.rows);
+    ui.vertical(|ui| {
+        ui.spacing_mut().item_spacing.y = 0.0;
+        ui.add_space(3.0);
+
+        for row in start..end {
+            let gap = text
+                .header_indices
+                .iter()
+                .position(|&r| r == row)
+                .and_then(|i| text.hunk_indices.get(i))
+                .map(|&hunk| gap(diff, hunk))
+                .filter(|gap| !gap.is_empty());
+            let Some(gap) = gap else {
+                ui.label(RichText::new(" ").monospace());
+                continue;
+            };
+
+            ui.horizontal(|ui| {
+                ui.spacing_mut().item_spacing.x = 2.0;
+                let hidden = gap.end - gap.start;
+                let mut expand = None;
+                if hidden > STEP {
+                    if button(ui, "↑", &format!("Show {STEP} more lines above")) {
+                        expand = Some(gap.end - STEP..gap.end);
+                    }
+                    // nothing is hidden above the first hunk except the start of the file
+                    if gap.start > 1 && button(ui, "↓", &format!("Show {STEP} more lines below"))
+                    {
+                        expand = Some(gap.start..gap.start + STEP);
+                    }
+                }
+                if button(ui, "⇕", &format!("Show all {hidden} hidden lines")) {
+                    expand = Some(gap);
+                }
+
+                if let Some(range) = expand {
+                    control_data.load_options.diff_settings.expand(
+                        diff.file_name(),
+                        diff.new_id,
+                        range,
+                    );
+                    control_data.refresh();
+                }
+            });
+        }
+    });
+}
+
+/// Lines of the new side hidden between the hunk and the one before it.
+fn gap(diff: &Diff, hunk: usize) -> Range<u32> {
+    let Some(header) = diff.headers.get(hunk) else {
+        return 0..0;
+    };
+    let previous_end = match hunk.checked_sub(1).and_then(|i| diff.headers.get(i)) {
+        Some(previous) => new_start(previous) + previous.lines,
+        None => 1,
+    };
+
+    previous_end..new_start(header).max(previous_end)
+}
+
+fn button(ui: &mut Ui, glyph: &str, hover: &str) -> bool {
+    ui.add(Label::new(RichText::new(glyph).monospace().color(COLOR)).sense(Sense::click()))
+        .on_hover_text(hover)
+        .clicked()
+}
@@ -1,6 +1,11 @@
 use egui::{Button, Color32, RichText, ScrollArea, Ui};
+use git2::Delta;
 
-use crate::data::{AppData, ControlData, DiffData, DiffType, Tree};
+use crate::{
+    data::{AppData, ControlData, DiffData, DiffType, FileLogSelection, Tree},
+    git,
+    ui::stage_area,
+};
 
 pub fn ui(
     ui: &mut Ui,
@@ -38,11 +43,8 @@ fn show_tree(
                 ui.add_space(10.0);
             }
             if ui.add(button).clicked() {
-                match control_data.diff_type {
-                    DiffType::Modified => {
-                        app_data.modified_diff_data.file_tree.toggle_open(tree.id)
-                    }
-                    DiffType::Staged => app_data.staged_diff_data.file_tree.toggle_open(tree.id),
+                if let Some(diff_data) = app_data.diff_data_mut(&control_data.diff_type) {
+                    diff_data.file_tree.toggle_open(tree.id);
                 }
             };
         });
@@ -72,9 +74,78 @@ fn show_tree(
                 Button::new(format!("🖹 {}", file.clone().get_name().unwrap())).frame(false)
             };
 
-            if ui.add(button).clicked() {
+            let toggle = match control_data.diff_type {
+                DiffType::Modified => Some(("+", "Stage file")),
+                DiffType::Staged => Some(("−", "Unstage file")),
+                _ => None,
+            };
+            if let Some((label, hover_text)) = toggle {
+                if ui.small_button(label).on_hover_text(hover_text).clicked() {
+                    stage_area::toggle_file(
+                        &app_data.project_path,
+                        &file.path,
+                        file.old_path
+                            .as_deref()
+                            .filter(|_| file.status == Delta::Renamed),
+                        control_data,
+                    );
+                }
+            }
+
+            let response = ui.add(button);
+            if response.clicked() {
                 control_data.selected_diff = file.path.clone();
+                control_data.selected_lines.clear();
+            }
+            response.context_menu(|ui| {
+                if ui.button("History").clicked() {
+                    control_data.file_log = Some(FileLogSelection {
+                        path: file.path.clone(),
+                        follow: true,
+                    });
+                    control_data.log_open = true;
+                    ui.close_menu();
+                }
+            });
+
+            if let Some(old_path) = &file.old_path {
+                ui.label(
+                    RichText::new(git::rename_label(old_path, &file.path, file.similarity))
+                        .small()
+                        .color(Color32::GRAY),
+                );
+            }
+
+            match file.status {
+                Delta::Untracked => {
+                    ui.label(RichText::new("untracked").small().color(Color32::YELLOW));
+                }
+                Delta::Conflicted => {
+                    ui.label(RichText::new("conflict").small().color(Color32::RED));
+                }
+                _ => (),
+            }
+
+            if let Some(binary) = file.binary {
+                ui.label(RichText::new("binary").small().color(Color32::LIGHT_BLUE));
+                ui.label(
+                    RichText::new(format!(
+                        "{} → {}",
+                        format_size(binary.old_size),
+                        format_size(binary.new_size)
+                    ))
+                    .small()
+                    .color(Color32::GRAY),
+                );
             }
         });
     }
 }
+
+pub fn format_size(bytes: u64) -> String {
+    match bytes {
+        0..=1023 => format!("{bytes} B"),
+        1024..=1_048_575 => format!("{:.1} KiB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MiB", bytes as f64 / 1_048_576.0),
+    }
+}
@@ -0,0 +1,194 @@
+use std::sync::Arc;
+
+use egui::{
+    pos2,
+    util::cache::{ComputerMut, FrameCache},
+    Color32, Image, Rect, RichText, Sense, Slider, Stroke, TextureOptions, Ui, Vec2,
+};
+
+use crate::{
+    data::{ControlData, ImageMode},
+    git::image::{self, Images},
+    ui::files_area::format_size,
+};
+
+const MAX_HEIGHT: f32 = 400.0;
+
+pub fn ui(ui: &mut Ui, images: &Images, project_path: &String, control_data: &mut ControlData) {
+    puffin::profile_function!();
+
+    let both = images.old.is_some() && images.new.is_some();
+    ui.horizontal(|ui| {
+        ui.selectable_value(
+            &mut control_data.image_mode,
+            ImageMode::SideBySide,
+            "Side by side",
+        );
+        ui.add_enabled_ui(both, |ui| {
+            ui.selectable_value(&mut control_data.image_mode, ImageMode::Swipe, "Swipe");
+            ui.selectable_value(
+                &mut control_data.image_mode,
+                ImageMode::OnionSkin,
+                "Onion skin",
+            );
+        });
+        if both && control_data.image_mode != ImageMode::SideBySide {
+            ui.add(Slider::new(&mut control_data.image_blend, 0.0..=1.0).show_value(false));
+        }
+    });
+
+    let (Some(old_bytes), Some(new_bytes)) = (
+        content(ui, project_path, images.old.as_ref()),
+        content(ui, project_path, images.new.as_ref()),
+    ) else {
+        return;
+    };
+    let old = images.old.as_ref().zip(old_bytes.clone()).map(texture);
+    let new = images.new.as_ref().zip(new_bytes.clone()).map(texture);
+    let (Some(old_size), Some(new_size)) = (size(ui, &old), size(ui, &new)) else {
+        return;
+    };
+
+    ui.horizontal(|ui| {
+        describe(ui, "Old", old_bytes.as_deref(), old_size);
+        ui.separator();
+        describe(ui, "New", new_bytes.as_deref(), new_size);
+    });
+
+    let bounds = old_size.max(new_size);
+    let mode = if both {
+        control_data.image_mode
+    } else {
+        ImageMode::SideBySide
+    };
+    let width = match mode {
+        ImageMode::SideBySide => (ui.available_width() - ui.spacing().item_spacing.x) / 2.0,
+        _ => ui.available_width(),
+    };
+    let scale = (width / bounds.x).min(MAX_HEIGHT / bounds.y);
+
+    match (mode, old, new) {
+        (ImageMode::Swipe, Some(old), Some(new)) => {
+            let (rect, response) = ui.allocate_exact_size(bounds * scale, Sense::drag());
+            if let Some(pointer) = response.interact_pointer_pos() {
+                control_data.image_blend =
+                    ((pointer.x - rect.left()) / rect.width()).clamp(0.0, 1.0);
+            }
+
+            // old on the left of the divider, new on the right
+            let x = rect.left() + rect.width() * control_data.image_blend;
+            let left = Rect::from_min_max(rect.min, pos2(x, rect.bottom()));
+            let right = Rect::from_min_max(pos2(x, rect.top()), rect.max);
+            old.paint_at(&clipped(ui, rect, left), fit(rect, old_size, scale));
+            new.paint_at(&clipped(ui, rect, right), fit(rect, new_size, scale));
+            ui.painter()
+                .vline(x, rect.y_range(), Stroke::new(1.0, Color32::WHITE));
+        }
+        (ImageMode::OnionSkin, Some(old), Some(new)) => {
+            let (rect, _) = ui.allocate_exact_size(bounds * scale, Sense::hover());
+            old.paint_at(ui, fit(rect, old_size, scale));
+            new.tint(Color32::from_white_alpha(
+                (control_data.image_blend * 255.0) as u8,
+            ))
+            .paint_at(ui, fit(rect, new_size, scale));
+        }
+        (_, old, new) => {
+            ui.horizontal(|ui| {
+                for (image, size) in [(old, old_size), (new, new_size)] {
+                    let (rect, _) = ui.allocate_exact_size(bounds * scale, Sense::hover());
+                    match image {
+                        Some(image) => image.paint_at(ui, fit(rect, size, scale)),
+                        None => {
+                            ui.painter().rect_stroke(
+                                rect,
+                                0.0,
+                                Stroke::new(1.0, Color32::DARK_GRAY),
+                            );
+                        }
+                    }
+                }
+            });
+        }
+    }
+}
+
+fn texture((image, bytes): (&image::Image, Arc<[u8]>)) -> Image<'static> {
+    Image::from_bytes(image.uri.clone(), bytes).texture_options(TextureOptions::NEAREST)
+}
+
+/// The content of a side, `Some(None)` for a missing side and `None` if it can't be read. Each
+/// side is read once its diff is opened and kept in memory for as long as it is shown.
+fn content(ui: &mut Ui, path: &String, image: Option<&image::Image>) -> Option<Option<Arc<[u8]>>> {
+    let Some(image) = image else {
+        return Some(None);
+    };
+
+    let content = ui
+        .ctx()
+        .memory_mut(|mem| mem.caches.cache::<ContentCache>().get((path, image)));
+    match content {
+        Ok(bytes) => Some(Some(bytes)),
+        Err(e) => {
+            ui.label(RichText::new(format!("Can't read image: {e}")).color(Color32::RED));
+            None
+        }
+    }
+}
+
+type ContentCache = FrameCache<Result<Arc<[u8]>, String>, ContentReader>;
+
+#[derive(Default)]
+struct ContentReader {}
+
+impl ComputerMut<(&String, &image::Image), Result<Arc<[u8]>, String>> for ContentReader {
+    fn compute(&mut self, (path, image): (&String, &image::Image)) -> Result<Arc<[u8]>, String> {
+        puffin::profile_function!();
+        image::read(path, image).map_err(|e| e.to_string())
+    }
+}
+
+/// The size of the decoded image, `Vec2::ZERO` for a missing side and `None` while it is still
+/// loading or can't be decoded.
+fn size(ui: &mut Ui, image: &Option<Image>) -> Option<Vec2> {
+    let Some(image) = image else {
+        return Some(Vec2::ZERO);
+    };
+
+    match image.load_for_size(ui.ctx(), Vec2::splat(MAX_HEIGHT)) {
+        Ok(poll) => match poll.size() {
+            Some(size) => Some(size),
+            None => {
+                ui.spinner();
+                None
+            }
+        },
+        Err(e) => {
+            ui.label(RichText::new(format!("Can't show image: {e}")).color(Color32::RED));
+            None
+        }
+    }
+}
+
+fn describe(ui: &mut Ui, label: &str, bytes: Option<&[u8]>, size: Vec2) {
+    let text = match bytes {
+        Some(bytes) => format!(
+            "{label}: {}×{}, {}",
+            size.x,
+            size.y,
+            format_size(bytes.len() as u64)
+        ),
+        None => format!("{label}: none"),
+    };
+    ui.label(RichText::new(text).color(Color32::GRAY));
+}
+
+/// Images of different sizes are aligned at the top left corner.
+fn fit(rect: Rect, size: Vec2, scale: f32) -> Rect {
+    Rect::from_min_size(rect.min, size * scale)
+}
+
+fn clipped(ui: &mut Ui, rect: Rect, clip: Rect) -> Ui {
+    let mut child = ui.child_ui(rect, *ui.layout());
+    child.set_clip_rect(clip.intersect(ui.clip_rect()));
+    child
+}
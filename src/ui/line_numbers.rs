@@ -1,11 +1,11 @@
 use egui::{Color32, Response, RichText, Ui};
 
-use crate::git::Diff;
+use crate::git::text::DiffText;
 
-pub fn ui(ui: &mut Ui, diff: &Diff, start: usize, end: usize) -> Response {
+pub fn ui(ui: &mut Ui, text: &DiffText, start: usize, end: usize) -> Response {
     puffin::profile_function!("line_numbers::ui");
 
-    let lines = diff.line_numbers.lines().collect::<Vec<&str>>();
+    let lines = text.line_numbers.lines().collect::<Vec<&str>>();
     let end = std::cmp::min(end, lines.len());
 
     let content = &lines[start..end].join("\n");
@@ -1,10 +1,39 @@
-use std::ops::Range;
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Range,
+    path::Path,
+};
 
-use egui::{Color32, Label, RichText, ScrollArea, Sense, Ui};
+use egui::{
+    pos2, Button, Color32, Label, Rect, Response, RichText, ScrollArea, Sense, Shape, Stroke, Ui,
+};
 
-use crate::{data::ControlData, git::commit::Commit};
+use crate::{
+    data::{CommitSelection, ControlData, DiffType, FileLog, Log, RefFilter, Search},
+    git::{
+        commit::{Commit, Reference, ReferenceKind},
+        graph::GraphRow,
+        search::Query,
+    },
+};
 
-pub fn ui(ui: &mut Ui, commits: &[Commit], control_data: &mut ControlData) {
+const LANE_WIDTH: f32 = 14.0;
+const LANE_COLORS: [Color32; 6] = [
+    Color32::from_rgb(7, 138, 171),
+    Color32::from_rgb(220, 160, 40),
+    Color32::from_rgb(80, 180, 80),
+    Color32::from_rgb(200, 80, 160),
+    Color32::from_rgb(220, 90, 70),
+    Color32::from_rgb(140, 120, 220),
+];
+
+pub fn ui(
+    ui: &mut Ui,
+    log: &mut Log,
+    file_log: Option<&FileLog>,
+    search: Option<&Search>,
+    control_data: &mut ControlData,
+) {
     puffin::profile_function!();
     ui.add_space(10.0);
 
@@ -18,44 +47,304 @@ pub fn ui(ui: &mut Ui, commits: &[Commit], control_data: &mut ControlData) {
 
         ui.horizontal(|ui| {
             ui.label("Search:");
-            ui.text_edit_singleline(&mut control_data.search_string);
+            ui.text_edit_singleline(&mut control_data.search_string)
+                .on_hover_text(
+                    "Regex over message and author, combined with\n\
+                     author:<name>  since:<yyyy-mm-dd>  until:<yyyy-mm-dd>\n\
+                     path:<path>  -S<string> (changes the number of occurrences)\n\
+                     Use \"double quotes\" for values with spaces",
+                );
         });
 
+        if let Some(search) = search {
+            match &search.query {
+                Ok(_) => {
+                    ui.horizontal(|ui| {
+                        ui.label(format!("{} found", search.commits.len()));
+                        if !search.complete {
+                            ui.spinner();
+                        }
+                    });
+                }
+                Err(error) => {
+                    ui.label(RichText::new(error).color(Color32::RED));
+                }
+            }
+        }
+
+        if ui
+            .checkbox(
+                &mut control_data.load_options.log_all_branches,
+                "All branches",
+            )
+            .on_hover_text("Include all local branches and remote-tracking refs")
+            .changed()
+        {
+            control_data.refresh();
+        }
+
+        if let Some(filter) = &control_data.ref_filter {
+            let mut clear = false;
+            ui.horizontal(|ui| {
+                ui.label(format!("History of {}", filter.name));
+                clear = ui.small_button("Clear").clicked();
+            });
+            if clear {
+                control_data.ref_filter = None;
+            }
+        }
+
+        if let Some(selection) = &mut control_data.file_log {
+            let mut close = false;
+            ui.horizontal(|ui| {
+                ui.label(format!("History of {}", selection.path.display()));
+                ui.checkbox(&mut selection.follow, "Follow renames");
+                close = ui.small_button("Close").clicked();
+            });
+            if close {
+                control_data.file_log = None;
+            }
+        }
+
         ui.separator();
 
-        let commits: Vec<&Commit> = commits
-            .iter()
-            .filter(|c| c.contains(&control_data.search_string))
-            .collect();
+        if control_data.file_log.is_some() {
+            let query = search.and_then(|s| s.query.as_ref().ok());
+            show_file_log(ui, file_log, query, control_data);
+            return;
+        }
+
+        let commits: Vec<(&Commit, Option<&GraphRow>)> = match search {
+            Some(search) => search.commits.iter().map(|c| (c, None)).collect(),
+            None => log
+                .commits
+                .iter()
+                .zip(&log.graph.rows)
+                .map(|(c, row)| (c, Some(row)))
+                .collect(),
+        };
+        // the graph only makes sense for the complete history
+        let commits: Vec<(&Commit, Option<&GraphRow>)> = match &control_data.ref_filter {
+            Some(filter) => commits
+                .into_iter()
+                .filter(|(c, _)| filter.ids.contains(&c.id))
+                .map(|(c, _)| (c, None))
+                .collect(),
+            None => commits,
+        };
+        let loading = search.map_or(log.loading, |s| !s.complete);
+
+        let mut clicked_ref = None;
+        let mut near_end = commits.is_empty();
 
         // consider using https://github.com/emilk/egui/issues/1376
         ScrollArea::vertical()
             .id_source("history scroll area")
             .show_rows(ui, 100.0, commits.len(), |ui, row_range| {
                 let Range { start, end } = row_range;
-                for commit in &commits[start..end] {
-                    show_commit(ui, commit)
+                near_end = end + 20 >= commits.len();
+                let lanes = commits[start..end]
+                    .iter()
+                    .filter_map(|(_, row)| row.map(|r| r.width))
+                    .max()
+                    .unwrap_or(0);
+                for (commit, row) in &commits[start..end] {
+                    let response = ui
+                        .horizontal_top(|ui| {
+                            ui.add_space(lanes as f32 * LANE_WIDTH);
+                            ui.vertical(|ui| {
+                                if let Some(name) = show_commit(ui, commit, None, control_data) {
+                                    clicked_ref = Some((name, commit.id.clone()));
+                                }
+                            });
+                        })
+                        .response;
+                    if let Some(row) = row {
+                        paint_graph(ui, response.rect, row, commit.parents.len() > 1);
+                    }
+                }
+
+                if loading {
+                    ui.spinner();
                 }
             });
+
+        if let Some((name, id)) = clicked_ref {
+            control_data.ref_filter = Some(RefFilter {
+                name,
+                ids: reachable(&log.commits, &id),
+            });
+        }
+
+        // the rest of the history is only loaded while scrolling towards it
+        if near_end && search.is_none() {
+            log.load_more();
+        }
     });
 }
 
-fn show_commit(ui: &mut Ui, commit: &Commit) {
+fn show_file_log(
+    ui: &mut Ui,
+    file_log: Option<&FileLog>,
+    query: Option<&Query>,
+    control_data: &mut ControlData,
+) {
     puffin::profile_function!();
 
-    if ui
-        .add(
-            Label::new(RichText::new(format!("commit {}", commit.id)).color(Color32::LIGHT_BLUE))
-                .sense(Sense::click()),
-        )
-        .on_hover_text_at_pointer("Click to copy id")
-        .clicked()
-    {
-        ui.output_mut(|po| {
-            po.copied_text = commit.clone().id;
+    let Some(file_log) = file_log else {
+        return;
+    };
+
+    let commits: Vec<_> = file_log
+        .commits
+        .iter()
+        .filter(|c| match query {
+            Some(query) => query.matches(&c.commit),
+            None => true,
+        })
+        .collect();
+
+    ScrollArea::vertical()
+        .id_source("file history scroll area")
+        .show_rows(ui, 100.0, commits.len(), |ui, row_range| {
+            for file_commit in &commits[row_range] {
+                if file_commit.path != file_log.selection.path {
+                    ui.label(
+                        RichText::new(format!("as {}", file_commit.path.display()))
+                            .small()
+                            .color(Color32::GRAY),
+                    );
+                }
+                show_commit(
+                    ui,
+                    &file_commit.commit,
+                    Some(&file_commit.path),
+                    control_data,
+                );
+            }
+
+            if !file_log.complete {
+                ui.spinner();
+            }
+        });
+}
+
+/// Ids of the commits in the log reachable from the given commit.
+fn reachable(commits: &[Commit], id: &str) -> HashSet<String> {
+    let by_id: HashMap<&str, &Commit> = commits.iter().map(|c| (c.id.as_str(), c)).collect();
+    let mut ids = HashSet::new();
+    let mut pending = vec![id];
+
+    while let Some(id) = pending.pop() {
+        let Some(commit) = by_id.get(id) else {
+            continue;
+        };
+        if ids.insert(commit.id.clone()) {
+            pending.extend(commit.parents.iter().map(String::as_str));
+        }
+    }
+
+    ids
+}
+
+fn paint_graph(ui: &Ui, rect: Rect, row: &GraphRow, merge: bool) {
+    puffin::profile_function!();
+
+    let painter = ui.painter();
+    let gap = ui.spacing().item_spacing.y / 2.0;
+    let (top, bottom) = (rect.top() - gap, rect.bottom() + gap);
+    let center = rect.top() + 8.0;
+    let x = |lane: usize| rect.left() + LANE_WIDTH * (lane as f32 + 0.5);
+    let color = |lane: usize| LANE_COLORS[lane % LANE_COLORS.len()];
+
+    // lanes change close to the commit and run straight otherwise
+    for (from, to) in &row.upper {
+        let points = vec![
+            pos2(x(*from), top),
+            pos2(x(*from), (center - LANE_WIDTH).max(top)),
+            pos2(x(*to), center),
+        ];
+        painter.add(Shape::line(points, Stroke::new(2.0, color(*from))));
+    }
+    for (from, to) in &row.lower {
+        let points = vec![
+            pos2(x(*from), center),
+            pos2(x(*to), (center + LANE_WIDTH).min(bottom)),
+            pos2(x(*to), bottom),
+        ];
+        painter.add(Shape::line(points, Stroke::new(2.0, color(*to))));
+    }
+
+    let dot = pos2(x(row.lane), center);
+    if merge {
+        painter.circle(dot, 4.0, Color32::BLACK, Stroke::new(2.0, color(row.lane)));
+    } else {
+        painter.circle_filled(dot, 4.0, color(row.lane));
+    }
+}
+
+/// Returns the name of a reference badge if it was clicked. Clicking the commit selects `file`
+/// in its diff.
+fn show_commit(
+    ui: &mut Ui,
+    commit: &Commit,
+    file: Option<&Path>,
+    control_data: &mut ControlData,
+) -> Option<String> {
+    puffin::profile_function!();
+
+    let mut clicked_ref = None;
+    if !commit.refs.is_empty() {
+        ui.horizontal_wrapped(|ui| {
+            for reference in &commit.refs {
+                if badge(ui, reference)
+                    .on_hover_text("Click to show its history")
+                    .clicked()
+                {
+                    clicked_ref = Some(reference.name.clone());
+                }
+            }
         });
     }
 
+    ui.horizontal(|ui| {
+        if ui
+            .add(
+                Label::new(
+                    RichText::new(format!("commit {}", commit.id)).color(Color32::LIGHT_BLUE),
+                )
+                .sense(Sense::click()),
+            )
+            .on_hover_text_at_pointer("Click to show diff")
+            .clicked()
+        {
+            control_data.load_options.commit = Some(CommitSelection {
+                id: commit.id.clone(),
+                parent: 0,
+            });
+            control_data.diff_type = DiffType::Commit;
+            if let Some(file) = file {
+                control_data.selected_diff = file.to_path_buf();
+                control_data.selected_lines.clear();
+            }
+            control_data.refresh();
+        }
+
+        if ui.small_button("Copy").on_hover_text("Copy id").clicked() {
+            ui.output_mut(|po| {
+                po.copied_text = commit.clone().id;
+            });
+        }
+        if ui
+            .small_button("Branch")
+            .on_hover_text("Create a branch at this commit")
+            .clicked()
+        {
+            control_data.branch_start = Some(commit.id.clone());
+            control_data.branch_open = true;
+        }
+    });
+
     ui.label(
         RichText::new(format!(
             "Author: {} <{}>",
@@ -70,4 +359,21 @@ fn show_commit(ui: &mut Ui, commit: &Commit) {
         ui.label(RichText::new(commit.message.to_string()).color(Color32::WHITE));
     });
     ui.separator();
+
+    clicked_ref
+}
+
+fn badge(ui: &mut Ui, reference: &Reference) -> Response {
+    let color = match reference.kind {
+        ReferenceKind::Head => Color32::from_rgb(7, 138, 171),
+        ReferenceKind::Branch => Color32::from_rgb(80, 180, 80),
+        ReferenceKind::Remote => Color32::from_rgb(220, 90, 70),
+        ReferenceKind::Tag => Color32::from_rgb(220, 160, 40),
+    };
+
+    ui.add(
+        Button::new(RichText::new(&reference.name).color(Color32::BLACK))
+            .fill(color)
+            .small(),
+    )
 }
@@ -0,0 +1,137 @@
+use anyhow::Result;
+use egui::{Button, Checkbox, Color32, RichText, TextEdit, TextStyle, Ui};
+
+use crate::{
+    data::{AppData, CommitRequest, ControlData},
+    git::commit,
+};
+
+/// Summaries up to this length are shown in full by most tools.
+const SUMMARY_LENGTH: usize = 50;
+/// Beyond this the summary gets cut off almost everywhere.
+const MAX_SUMMARY_LENGTH: usize = 72;
+
+pub fn ui(ui: &mut Ui, app_data: &AppData, control_data: &mut ControlData) {
+    puffin::profile_function!();
+    ui.add_space(5.0);
+
+    ui.horizontal(|ui| {
+        match &app_data.author {
+            Some(author) => ui.label(
+                RichText::new(format!("Author: {} <{}>", author.name, author.email))
+                    .color(Color32::WHITE),
+            ),
+            None => ui.label(
+                RichText::new("No author configured, set user.name and user.email")
+                    .color(Color32::RED),
+            ),
+        };
+        ui.separator();
+        let amend = Checkbox::new(&mut control_data.commit_amend, "Amend");
+        if ui
+            .add_enabled(!control_data.committing, amend)
+            .on_hover_text("Replace the last commit")
+            .changed()
+        {
+            toggle_amend(&app_data.project_path, control_data);
+        }
+        ui.checkbox(&mut control_data.commit_skip_hooks, "Skip hooks")
+            .on_hover_text("Don't run the pre-commit and commit-msg hooks");
+    });
+
+    ui.add_enabled(
+        !control_data.committing,
+        TextEdit::multiline(&mut control_data.commit_message)
+            .font(TextStyle::Monospace)
+            .hint_text("Summary\n\nDescription")
+            .desired_rows(4)
+            .desired_width(f32::INFINITY),
+    );
+
+    ui.horizontal(|ui| {
+        let has_changes = control_data.commit_amend || !app_data.staged_diff_data.diffs.is_empty();
+        let can_commit = app_data.author.is_some()
+            && has_changes
+            && !control_data.commit_message.trim().is_empty();
+        let label = if control_data.commit_amend {
+            "Amend"
+        } else {
+            "Commit"
+        };
+        if ui
+            .add_enabled(can_commit && !control_data.committing, Button::new(label))
+            .clicked()
+        {
+            control_data.commit_request = Some(CommitRequest {
+                message: control_data.commit_message.clone(),
+                amend: control_data.commit_amend,
+                run_hooks: !control_data.commit_skip_hooks,
+            });
+        }
+        if control_data.committing {
+            ui.spinner();
+        }
+        summary_hints(ui, &control_data.commit_message);
+    });
+    ui.add_space(5.0);
+}
+
+fn summary_hints(ui: &mut Ui, message: &str) {
+    let mut lines = message.lines();
+    let length = lines.next().unwrap_or("").chars().count();
+    let (color, hint) = match length {
+        0..=SUMMARY_LENGTH => (Color32::GRAY, None),
+        l if l <= MAX_SUMMARY_LENGTH => (
+            Color32::YELLOW,
+            Some(format!(
+                "Try to keep the summary within {SUMMARY_LENGTH} characters"
+            )),
+        ),
+        _ => (
+            Color32::RED,
+            Some(format!(
+                "Summaries longer than {MAX_SUMMARY_LENGTH} characters get cut off"
+            )),
+        ),
+    };
+
+    let response = ui.label(RichText::new(format!("{length}/{SUMMARY_LENGTH}")).color(color));
+    if let Some(hint) = hint {
+        response.on_hover_text(hint);
+    }
+
+    if lines.next().is_some_and(|l| !l.trim().is_empty()) {
+        ui.label(
+            RichText::new("Separate the summary from the description with an empty line")
+                .color(Color32::YELLOW),
+        );
+    }
+}
+
+/// Amending starts from the previous message, unless something has been written already.
+fn toggle_amend(path: &String, control_data: &mut ControlData) {
+    let previous = match commit::head_message(path) {
+        Ok(message) => message,
+        Err(e) => {
+            control_data.commit_amend = false;
+            control_data.show_error(format!("Error loading the previous commit: {e}"));
+            return;
+        }
+    };
+
+    if control_data.commit_amend && control_data.commit_message.trim().is_empty() {
+        control_data.commit_message = previous;
+    } else if !control_data.commit_amend && control_data.commit_message == previous {
+        control_data.commit_message.clear();
+    }
+}
+
+/// Takes the result of a commit created in the background.
+pub fn finish(result: Result<()>, control_data: &mut ControlData) {
+    control_data.committing = false;
+    if result.is_ok() {
+        control_data.commit_message.clear();
+        control_data.commit_amend = false;
+    }
+    control_data.finish(result, "Error creating commit");
+}
@@ -1,21 +1,38 @@
 use std::sync::mpsc::Sender;
 
-use egui::{Align, Color32, Context, Layout, RichText, Ui, Window};
+use egui::{
+    Align, Checkbox, Color32, Context, Layout, RichText, Slider, TopBottomPanel, Ui, Window,
+};
+use git2::Delta;
 
 use crate::{
-    data::{DiffType, Message},
+    data::{Blame, DiffType, Message},
+    git::settings::DiffAlgorithm,
     AppData, ControlData,
 };
 
+mod blame;
+pub mod branch_area;
 mod code;
+mod commit_area;
+mod conflict_area;
 mod diff_area;
 mod diff_type;
+mod discard_area;
+mod expand;
 mod files_area;
+mod image_area;
 mod line_numbers;
 pub mod log;
+pub mod message_area;
+mod moved;
 mod origins;
+mod revision_area;
 mod selection_area;
+mod stage_area;
+pub mod stash_area;
 mod stats;
+mod syntax;
 
 pub fn selection(
     ui: &mut Ui,
@@ -32,15 +49,18 @@ pub fn selection(
     selection_area::ui(ctx, ui, sender, control_data);
 }
 
-pub fn main(ui: &mut Ui, app_data: &mut AppData, control_data: &mut ControlData) {
+pub fn main(
+    ui: &mut Ui,
+    app_data: &mut AppData,
+    blame: Option<&Blame>,
+    control_data: &mut ControlData,
+) {
     puffin::profile_function!();
 
-    let diff_data = match control_data.diff_type {
-        DiffType::Modified => app_data.modified_diff_data.clone(),
-        DiffType::Staged => app_data.staged_diff_data.clone(),
-    };
-
-    ui.heading(RichText::new(&app_data.project_path).color(Color32::WHITE));
+    ui.horizontal(|ui| {
+        ui.heading(RichText::new(&app_data.project_path).color(Color32::WHITE));
+        branch_area::header(ui, &app_data.head, control_data);
+    });
     ui.separator();
 
     ui.horizontal(|ui| {
@@ -52,8 +72,148 @@ pub fn main(ui: &mut Ui, app_data: &mut AppData, control_data: &mut ControlData)
         {
             control_data.log_open = !control_data.log_open;
         }
+        if ui
+            .button(
+                RichText::new(format!("Stashes ({})", app_data.stashes.len()))
+                    .color(Color32::WHITE),
+            )
+            .clicked()
+        {
+            control_data.stash_open = !control_data.stash_open;
+        }
+        if ui
+            .button(
+                RichText::new(format!("Branches ({})", app_data.branches.len()))
+                    .color(Color32::WHITE),
+            )
+            .clicked()
+        {
+            control_data.branch_open = !control_data.branch_open;
+        }
+        ui.separator();
+        ui.checkbox(&mut control_data.split_view, "Split");
+        ui.menu_button("Whitespace", |ui| {
+            ui.checkbox(&mut control_data.show_whitespace, "Show whitespace")
+                .on_hover_text("Draw tabs, trailing spaces and carriage returns");
+            ui.separator();
+
+            let settings = &mut control_data.load_options.diff_settings;
+            let changed = [
+                ui.checkbox(&mut settings.ignore_whitespace, "Ignore all whitespace"),
+                ui.checkbox(
+                    &mut settings.ignore_whitespace_change,
+                    "Ignore whitespace changes",
+                )
+                .on_hover_text("Treat runs of whitespace as equal"),
+                ui.checkbox(
+                    &mut settings.ignore_whitespace_eol,
+                    "Ignore whitespace at line end",
+                ),
+                ui.add_enabled(
+                    settings.algorithm != DiffAlgorithm::Histogram,
+                    Checkbox::new(&mut settings.ignore_blank_lines, "Ignore blank lines"),
+                )
+                .on_disabled_hover_text("The histogram algorithm can't ignore blank lines"),
+            ]
+            .iter()
+            .any(|r| r.changed());
+            if changed {
+                control_data.refresh();
+            }
+        });
+        ui.menu_button("Algorithm", |ui| {
+            let algorithm = &mut control_data.load_options.diff_settings.algorithm;
+            let changed = DiffAlgorithm::ALL
+                .iter()
+                .map(|&a| ui.radio_value(algorithm, a, a.name()))
+                .fold(false, |changed, r| changed | r.changed());
+            if changed {
+                control_data.refresh();
+            }
+        })
+        .response
+        .on_hover_text("How changed lines are matched up");
+        ui.checkbox(&mut control_data.show_blame, "Blame")
+            .on_hover_text("Show who last changed each line");
+        if control_data.diff_type == DiffType::Modified
+            && ui
+                .checkbox(
+                    &mut control_data
+                        .load_options
+                        .diff_settings
+                        .recurse_untracked_dirs,
+                    "Recurse untracked directories",
+                )
+                .changed()
+        {
+            control_data.refresh();
+        }
+        ui.separator();
+        let similarity = ui
+            .add(
+                Slider::new(
+                    &mut control_data.load_options.diff_settings.similarity_threshold,
+                    1..=100,
+                )
+                .suffix("%")
+                .text("Rename similarity"),
+            )
+            .on_hover_text("Minimum similarity for files to be detected as renamed or copied");
+        if similarity.drag_released() || (similarity.changed() && !similarity.dragged()) {
+            control_data.refresh();
+        }
+        let context = ui
+            .add(
+                Slider::new(
+                    &mut control_data.load_options.diff_settings.context_lines,
+                    0..=20,
+                )
+                .text("Context lines"),
+            )
+            .on_hover_text("Unchanged lines shown around each change");
+        if context.drag_released() || (context.changed() && !context.dragged()) {
+            control_data.refresh();
+        }
+        if !control_data.discarded.is_empty() {
+            ui.separator();
+            if ui
+                .button(format!("Discarded ({})", control_data.discarded.len()))
+                .clicked()
+            {
+                control_data.show_discarded = !control_data.show_discarded;
+            }
+        }
     });
 
+    if control_data.show_discarded {
+        discard_area::window(ui.ctx(), &app_data.project_path, control_data);
+    }
+
+    if control_data.diff_type == DiffType::Revision {
+        revision_area::ui(ui, &app_data.project_path, control_data);
+    }
+
+    if control_data.diff_type == DiffType::Commit {
+        if let Some(commit_diff) = &app_data.commit_diff {
+            commit_area::ui(ui, &commit_diff.commit, control_data);
+        }
+    }
+
+    if control_data.diff_type == DiffType::Stash {
+        if let Some(stash_diff) = &app_data.stash_diff {
+            stash_area::header(ui, &stash_diff.stash);
+        }
+    }
+
+    if control_data.diff_type == DiffType::Staged {
+        TopBottomPanel::bottom("commit message panel")
+            .show_inside(ui, |ui| message_area::ui(ui, app_data, control_data));
+    }
+
+    let Some(diff_data) = app_data.diff_data(&control_data.diff_type).cloned() else {
+        return;
+    };
+
     ui.add_space(10.0);
     stats::ui(ui, &diff_data.stats);
     ui.separator();
@@ -66,8 +226,72 @@ pub fn main(ui: &mut Ui, app_data: &mut AppData, control_data: &mut ControlData)
 
             if let Some(diff) = diff_data.get_diff(&control_data.selected_diff) {
                 ui.vertical(|ui| {
-                    ui.label(control_data.selected_diff.to_str().unwrap());
-                    diff_area::ui(ui, &diff);
+                    ui.horizontal(|ui| {
+                        ui.label(diff.label());
+                        ui.label(
+                            RichText::new(control_data.load_options.diff_settings.algorithm.name())
+                                .small()
+                                .color(Color32::GRAY),
+                        );
+                        if diff.encoding != encoding_rs::UTF_8 {
+                            ui.label(
+                                RichText::new(diff.encoding.name())
+                                    .small()
+                                    .color(Color32::LIGHT_BLUE),
+                            );
+                        }
+                        if diff.lossy {
+                            ui.label(
+                                RichText::new("some bytes couldn't be decoded (shown as �)")
+                                    .small()
+                                    .color(Color32::YELLOW),
+                            );
+                        }
+                    });
+
+                    let conflict = app_data
+                        .conflicts
+                        .iter()
+                        .find(|c| c.path == diff.file_name());
+                    if let Some(conflict) = conflict.filter(|_| diff.status == Delta::Conflicted) {
+                        conflict_area::ui(ui, conflict, &app_data.project_path, control_data);
+                        return;
+                    }
+
+                    if matches!(
+                        control_data.diff_type,
+                        DiffType::Modified | DiffType::Staged
+                    ) {
+                        stage_area::ui(ui, &diff, &app_data.project_path, control_data);
+                    }
+                    if let Some(images) = &diff.images {
+                        image_area::ui(ui, images, &app_data.project_path, control_data);
+                    }
+                    match diff.binary {
+                        Some(binary) => {
+                            if diff.images.is_none() {
+                                ui.label(
+                                    RichText::new(format!(
+                                        "Binary file, {} → {}",
+                                        files_area::format_size(binary.old_size),
+                                        files_area::format_size(binary.new_size)
+                                    ))
+                                    .color(Color32::GRAY),
+                                );
+                            }
+                        }
+                        None => {
+                            if let Some(lines) = diff_area::ui(
+                                ui,
+                                &diff,
+                                blame,
+                                &app_data.project_path,
+                                control_data,
+                            ) {
+                                control_data.selected_lines = lines;
+                            }
+                        }
+                    }
                 });
             }
         }
@@ -0,0 +1,49 @@
+use egui::{Color32, Label, RichText, Sense, Ui};
+
+use crate::{
+    data::ControlData,
+    git::{text::DiffText, Diff},
+};
+
+const COLOR: Color32 = Color32::from_rgb(150, 110, 200);
+
+/// Marks the first row of each moved block, clicking it jumps to the other occurrence.
+pub fn ui(
+    ui: &mut Ui,
+    text: &DiffText,
+    diff: &Diff,
+    start: usize,
+    end: usize,
+    control_data: &mut ControlData,
+) {
+    puffin::profile_function!("moved::ui");
+
+    let end = std::cmp::min(end, text.rows);
+    ui.vertical(|ui| {
+        ui.spacing_mut().item_spacing.y = 0.0;
+        ui.add_space(3.0);
+
+        for row in start..end {
+            let block = text.line_indices[row]
+                .and_then(|i| diff.moves.iter().find(|block| block.lines.start == i));
+            let Some(block) = block else {
+                ui.label(RichText::new(" ").monospace());
+                continue;
+            };
+
+            let direction = if block.deleted { "to" } else { "from" };
+            let hover = format!(
+                "Moved {direction} {}:{}",
+                block.other_file.display(),
+                block.other_line_number
+            );
+            let response = ui
+                .add(Label::new(RichText::new("⇄").monospace().color(COLOR)).sense(Sense::click()))
+                .on_hover_text(hover);
+            if response.clicked() {
+                control_data.selected_diff = block.other_file.clone();
+                control_data.scroll_to_line = Some(block.other_lines.start);
+            }
+        }
+    });
+}
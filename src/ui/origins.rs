@@ -4,9 +4,9 @@ use egui::{
     Color32, Context, FontFamily, FontId, TextEdit, TextFormat, Ui,
 };
 
-use crate::git::Diff;
+use crate::git::text::DiffText;
 
-pub fn ui(ui: &mut Ui, diff: &Diff, start: usize, end: usize) {
+pub fn ui(ui: &mut Ui, text: &DiffText, start: usize, end: usize) {
     puffin::profile_function!("origins::ui");
 
     let mut layouter = |ui: &egui::Ui, string: &str, _wrap_width: f32| {
@@ -14,7 +14,7 @@ pub fn ui(ui: &mut Ui, diff: &Diff, start: usize, end: usize) {
         ui.fonts(|f| f.layout_job(layout_job))
     };
 
-    let lines = diff.origins_content.lines().collect::<Vec<&str>>();
+    let lines = text.origins_content.lines().collect::<Vec<&str>>();
     let end = std::cmp::min(end, lines.len());
 
     let mut content = lines[start..end].join("\n");
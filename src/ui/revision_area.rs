@@ -0,0 +1,47 @@
+use egui::{Color32, RichText, TextEdit, Ui};
+
+use crate::{data::ControlData, git::revision::RevisionRange};
+
+pub fn ui(ui: &mut Ui, project_path: &String, control_data: &mut ControlData) {
+    puffin::profile_function!();
+
+    ui.horizontal(|ui| {
+        ui.label("From:");
+        ui.add(
+            TextEdit::singleline(&mut control_data.revision_from)
+                .hint_text("HEAD")
+                .desired_width(150.0),
+        );
+        ui.label("To:");
+        ui.add(
+            TextEdit::singleline(&mut control_data.revision_to)
+                .hint_text("HEAD")
+                .desired_width(150.0),
+        );
+        ui.checkbox(&mut control_data.revision_merge_base, "Merge base")
+            .on_hover_text("Compare against the merge base of both revisions (from...to)");
+
+        if ui
+            .button(RichText::new("Compare").color(Color32::WHITE))
+            .clicked()
+        {
+            let range = RevisionRange::new(
+                &control_data.revision_from,
+                &control_data.revision_to,
+                control_data.revision_merge_base,
+            );
+
+            match range.resolve(project_path) {
+                Ok(()) => {
+                    control_data.load_options.revision_range = Some(range);
+                    control_data.refresh();
+                }
+                Err(e) => control_data.show_error(format!("Error resolving {range}: {e}")),
+            }
+        }
+
+        if let Some(range) = &control_data.load_options.revision_range {
+            ui.label(RichText::new(range.to_string()).color(Color32::GRAY));
+        }
+    });
+}
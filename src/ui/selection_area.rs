@@ -3,7 +3,7 @@ use std::sync::mpsc::Sender;
 use egui::{Color32, Context, RichText, ScrollArea, Ui, Window};
 
 use crate::{
-    data::{ControlData, Message},
+    data::{ControlData, DiffType, LoadOptions, Message},
     load_repository,
 };
 
@@ -18,7 +18,16 @@ pub fn ui(ctx: &Context, ui: &mut Ui, sender: &Sender<Message>, control_data: &m
             .clicked()
         {
             if let Some(path) = rfd::FileDialog::new().pick_folder() {
-                load_repository(path, sender);
+                control_data.load_options = LoadOptions::default();
+                control_data.discarded.clear();
+                control_data.ref_filter = None;
+                if matches!(
+                    control_data.diff_type,
+                    DiffType::Revision | DiffType::Commit | DiffType::Stash
+                ) {
+                    control_data.diff_type = DiffType::Modified;
+                }
+                load_repository(path, LoadOptions::default(), sender);
             }
         }
 
@@ -0,0 +1,163 @@
+use std::path::Path;
+
+use egui::{Button, Color32, Label, RichText, Sense, Ui};
+use git2::Delta;
+
+use crate::{
+    data::{ControlData, DiffType},
+    git::{discard, stage, text::DiffText, Diff},
+    ui::discard_area,
+};
+
+const COLOR: Color32 = Color32::from_rgb(7, 138, 171);
+const WHITESPACE_TEXT: &str = "Lines can't be changed one by one while whitespace is ignored";
+
+pub fn ui(ui: &mut Ui, diff: &Diff, project_path: &String, control_data: &mut ControlData) {
+    puffin::profile_function!();
+
+    let staged = control_data.diff_type == DiffType::Staged;
+    let action = if staged { "Unstage" } else { "Stage" };
+    let partial = !control_data.load_options.diff_settings.ignores_whitespace();
+    let disabled_text = if partial {
+        "Select changed lines in the diff"
+    } else {
+        WHITESPACE_TEXT
+    };
+
+    ui.horizontal(|ui| {
+        if ui.button(format!("{action} file")).clicked() {
+            // a copy leaves its source alone, so only renames take the old path along
+            let old_path = (diff.status == Delta::Renamed).then(|| diff.old_file_name());
+            toggle_file(
+                project_path,
+                &diff.file_name(),
+                old_path.as_deref(),
+                control_data,
+            );
+        }
+        if !staged && ui.button("Discard file").clicked() {
+            let result = discard::discard_file(project_path, &diff.file_name());
+            discard_area::finish(result, project_path, control_data);
+        }
+
+        let selected: Vec<usize> = control_data
+            .selected_lines
+            .iter()
+            .copied()
+            .filter(|&i| {
+                diff.lines
+                    .get(i)
+                    .is_some_and(|l| matches!(l.origin, '+' | '-'))
+            })
+            .collect();
+
+        let button = Button::new(format!("{action} selected lines ({})", selected.len()));
+        if ui
+            .add_enabled(partial && !selected.is_empty(), button)
+            .on_disabled_hover_text(disabled_text)
+            .clicked()
+        {
+            stage_lines(project_path, diff, &selected, control_data);
+        }
+
+        if !staged
+            && ui
+                .add_enabled(
+                    partial && !selected.is_empty(),
+                    Button::new("Discard selected lines"),
+                )
+                .on_disabled_hover_text(disabled_text)
+                .clicked()
+        {
+            let result = discard::discard_lines(project_path, diff, &selected);
+            discard_area::finish(result, project_path, control_data);
+        }
+    });
+}
+
+/// Buttons on the header rows that stage, unstage or discard the whole hunk.
+pub fn hunks(
+    ui: &mut Ui,
+    text: &DiffText,
+    diff: &Diff,
+    project_path: &String,
+    start: usize,
+    end: usize,
+    control_data: &mut ControlData,
+) {
+    puffin::profile_function!("stage_area::hunks");
+
+    let staged = control_data.diff_type == DiffType::Staged;
+    let action = if staged { "Unstage" } else { "Stage" };
+    let partial = !control_data.load_options.diff_settings.ignores_whitespace();
+
+    let end = std::cmp::min(end, text.rows);
+    ui.vertical(|ui| {
+        ui.spacing_mut().item_spacing.y = 0.0;
+        ui.add_space(3.0);
+
+        for row in start..end {
+            let lines = text
+                .header_indices
+                .iter()
+                .position(|&r| r == row)
+                .and_then(|i| text.hunk_indices.get(i))
+                .and_then(|&hunk| diff.hunks.get(hunk));
+            let Some(lines) = lines else {
+                ui.label(RichText::new(" ").monospace());
+                continue;
+            };
+
+            ui.horizontal(|ui| {
+                let lines: Vec<usize> = lines.clone().collect();
+                if button(ui, partial, action) {
+                    stage_lines(project_path, diff, &lines, control_data);
+                }
+                if !staged && button(ui, partial, "Discard") {
+                    let result = discard::discard_lines(project_path, diff, &lines);
+                    discard_area::finish(result, project_path, control_data);
+                }
+            });
+        }
+    });
+}
+
+fn button(ui: &mut Ui, enabled: bool, action: &str) -> bool {
+    let label = Label::new(RichText::new(action).monospace().color(COLOR)).sense(Sense::click());
+    ui.add_enabled(enabled, label)
+        .on_hover_text(format!("{action} hunk"))
+        .on_disabled_hover_text(WHITESPACE_TEXT)
+        .clicked()
+}
+
+/// Stages the file in the Modified view and unstages it in the Staged view, renamed files take
+/// their old path along.
+pub fn toggle_file(
+    project_path: &String,
+    file: &Path,
+    old_path: Option<&Path>,
+    control_data: &mut ControlData,
+) {
+    let toggle = match control_data.diff_type {
+        DiffType::Staged => stage::unstage_file,
+        _ => stage::stage_file,
+    };
+    let result = toggle(project_path, file).and_then(|_| match old_path {
+        Some(old_path) => toggle(project_path, old_path),
+        None => Ok(()),
+    });
+    control_data.finish(result, "Error updating index");
+}
+
+fn stage_lines(
+    project_path: &String,
+    diff: &Diff,
+    lines: &[usize],
+    control_data: &mut ControlData,
+) {
+    let result = match control_data.diff_type {
+        DiffType::Staged => stage::unstage_lines(project_path, diff, lines),
+        _ => stage::stage_lines(project_path, diff, lines),
+    };
+    control_data.finish(result, "Error updating index");
+}
@@ -0,0 +1,151 @@
+use egui::{Color32, Label, RichText, ScrollArea, Sense, Ui};
+
+use crate::{
+    data::{AppData, ControlData, DiffType},
+    git::stash::{self, Stash},
+};
+
+enum Action {
+    Apply,
+    Pop,
+    Drop,
+}
+
+pub fn ui(ui: &mut Ui, app_data: &AppData, control_data: &mut ControlData) {
+    puffin::profile_function!();
+    ui.add_space(10.0);
+
+    ui.horizontal(|ui| {
+        ui.heading("Stashes");
+        if ui.button("Close").clicked() {
+            control_data.stash_open = false;
+        };
+    });
+
+    ui.horizontal(|ui| {
+        ui.label("Message:");
+        ui.text_edit_singleline(&mut control_data.stash_message);
+    });
+    ui.horizontal(|ui| {
+        ui.checkbox(&mut control_data.stash_untracked, "Include untracked");
+        if ui
+            .button("Stash changes")
+            .on_hover_text("Stash the changes of the index and working tree")
+            .clicked()
+        {
+            let result = stash::save(
+                &app_data.project_path,
+                &control_data.stash_message,
+                control_data.stash_untracked,
+            );
+            if result.is_ok() {
+                control_data.stash_message.clear();
+            }
+            control_data.finish(result, "Error stashing changes");
+        }
+    });
+    ui.separator();
+
+    let mut action = None;
+    ScrollArea::vertical()
+        .id_source("stash scroll area")
+        .show(ui, |ui| {
+            if app_data.stashes.is_empty() {
+                ui.label(RichText::new("No stashes").color(Color32::GRAY));
+            }
+
+            for stash in &app_data.stashes {
+                if let Some(a) = show_stash(ui, stash, control_data) {
+                    action = Some((a, stash));
+                }
+            }
+        });
+
+    let Some((action, stash)) = action else {
+        return;
+    };
+
+    // popped and dropped stashes can't be shown anymore
+    if matches!(action, Action::Pop | Action::Drop)
+        && control_data.load_options.stash.as_ref() == Some(&stash.id)
+    {
+        control_data.load_options.stash = None;
+        if control_data.diff_type == DiffType::Stash {
+            control_data.diff_type = DiffType::Modified;
+        }
+    }
+
+    let path = &app_data.project_path;
+    match action {
+        Action::Apply => {
+            control_data.finish(stash::apply(path, stash.index), "Error applying stash")
+        }
+        Action::Pop => control_data.finish(stash::pop(path, stash.index), "Error popping stash"),
+        Action::Drop => control_data.finish(stash::drop(path, stash.index), "Error dropping stash"),
+    }
+}
+
+/// Describes the stash whose diff is shown.
+pub fn header(ui: &mut Ui, stash: &Stash) {
+    ui.add_space(10.0);
+    ui.label(RichText::new(format!("stash@{{{}}}", stash.index)).color(Color32::LIGHT_BLUE));
+    ui.label(RichText::new(format!("Date: {}", stash.time)).color(Color32::WHITE));
+    ui.label(RichText::new(&stash.message).color(Color32::WHITE));
+}
+
+fn show_stash(ui: &mut Ui, stash: &Stash, control_data: &mut ControlData) -> Option<Action> {
+    let selected = control_data.load_options.stash.as_ref() == Some(&stash.id);
+    let color = if selected {
+        Color32::WHITE
+    } else {
+        Color32::LIGHT_BLUE
+    };
+
+    if ui
+        .add(
+            Label::new(RichText::new(format!("stash@{{{}}}", stash.index)).color(color))
+                .sense(Sense::click()),
+        )
+        .on_hover_text_at_pointer("Click to show diff")
+        .clicked()
+    {
+        control_data.load_options.stash = Some(stash.id.clone());
+        control_data.diff_type = DiffType::Stash;
+        control_data.refresh();
+    }
+    ui.label(RichText::new(&stash.message).color(Color32::WHITE));
+    ui.label(RichText::new(stash.time.to_string()).color(Color32::GRAY));
+
+    let mut action = None;
+    ui.horizontal(|ui| {
+        if ui.small_button("Apply").clicked() {
+            action = Some(Action::Apply);
+        }
+        if ui
+            .small_button("Pop")
+            .on_hover_text("Apply and drop")
+            .clicked()
+        {
+            action = Some(Action::Pop);
+        }
+
+        // dropping can't be undone, so it takes a second click
+        if control_data.stash_to_drop.as_ref() == Some(&stash.id) {
+            if ui
+                .small_button(RichText::new("Really drop?").color(Color32::RED))
+                .clicked()
+            {
+                action = Some(Action::Drop);
+                control_data.stash_to_drop = None;
+            }
+            if ui.small_button("Cancel").clicked() {
+                control_data.stash_to_drop = None;
+            }
+        } else if ui.small_button("Drop").clicked() {
+            control_data.stash_to_drop = Some(stash.id.clone());
+        }
+    });
+    ui.separator();
+
+    action
+}
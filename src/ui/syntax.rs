@@ -0,0 +1,129 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    ops::Range,
+    path::Path,
+    sync::{Arc, OnceLock},
+};
+
+use egui::{
+    util::cache::{ComputerMut, FrameCache},
+    Color32, Context,
+};
+use syntect::{
+    easy::HighlightLines,
+    highlighting::{Theme, ThemeSet},
+    parsing::{SyntaxReference, SyntaxSet},
+};
+
+use crate::git::text::DiffText;
+
+const THEME: &str = "base16-ocean.dark";
+
+/// Token colors of every row of a `DiffText`, header and filler rows have no tokens.
+#[derive(Default)]
+pub struct SyntaxRows {
+    id: u64,
+    pub rows: Vec<Vec<(Range<usize>, Color32)>>,
+}
+
+// the rows are fully determined by the id, hashing them on every frame would be wasteful
+impl Hash for SyntaxRows {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+pub fn highlight(ctx: &Context, text: &DiffText, file_name: &Path) -> Arc<SyntaxRows> {
+    puffin::profile_function!();
+
+    ctx.memory_mut(|mem| mem.caches.cache::<SyntaxCache>().get((text, file_name)))
+}
+
+type SyntaxCache = FrameCache<Arc<SyntaxRows>, SyntaxHandler>;
+
+#[derive(Default)]
+struct SyntaxHandler {}
+
+impl ComputerMut<(&DiffText, &Path), Arc<SyntaxRows>> for SyntaxHandler {
+    fn compute(&mut self, (text, file_name): (&DiffText, &Path)) -> Arc<SyntaxRows> {
+        puffin::profile_function!();
+        Arc::new(SyntaxHandler::syntax_rows(text, file_name))
+    }
+}
+
+impl SyntaxHandler {
+    fn syntax_rows(text: &DiffText, file_name: &Path) -> SyntaxRows {
+        let (syntax_set, theme) = assets();
+        let syntax = find_syntax(syntax_set, file_name);
+
+        let mut hasher = DefaultHasher::new();
+        (&text.content, file_name).hash(&mut hasher);
+
+        // deletions continue the old side and insertions the new one, like in the files they come
+        // from, context lines belong to both sides
+        let mut old = HighlightLines::new(syntax, theme);
+        let mut new = HighlightLines::new(syntax, theme);
+        let is = |indices: &[usize], row: usize| indices.binary_search(&row).is_ok();
+
+        let mut rows = Vec::with_capacity(text.rows);
+        for (i, line) in text.content.lines().enumerate() {
+            // header rows and the filler rows of the split view aren't part of either side
+            if text.line_indices.get(i).copied().flatten().is_none() {
+                rows.push(Vec::new());
+                continue;
+            }
+
+            let line_with_newline = format!("{line}\n");
+            let tokens = if is(&text.deletion_indices, i) {
+                old.highlight_line(&line_with_newline, syntax_set)
+            } else if is(&text.insertion_indices, i) {
+                new.highlight_line(&line_with_newline, syntax_set)
+            } else {
+                let _ = old.highlight_line(&line_with_newline, syntax_set);
+                new.highlight_line(&line_with_newline, syntax_set)
+            };
+            let Ok(tokens) = tokens else {
+                rows.push(Vec::new());
+                continue;
+            };
+
+            let mut start = 0;
+            let mut spans = Vec::new();
+            for (style, token) in tokens {
+                let end = std::cmp::min(start + token.len(), line.len());
+                if start < end {
+                    let color = style.foreground;
+                    spans.push((start..end, Color32::from_rgb(color.r, color.g, color.b)));
+                }
+                start = end;
+            }
+            rows.push(spans);
+        }
+
+        SyntaxRows {
+            id: hasher.finish(),
+            rows,
+        }
+    }
+}
+
+fn find_syntax<'a>(syntax_set: &'a SyntaxSet, file_name: &Path) -> &'a SyntaxReference {
+    let extension = file_name.extension().and_then(|e| e.to_str());
+    let name = file_name.file_name().and_then(|n| n.to_str());
+
+    extension
+        .and_then(|e| syntax_set.find_syntax_by_extension(e))
+        .or_else(|| name.and_then(|n| syntax_set.find_syntax_by_extension(n)))
+        .unwrap_or_else(|| syntax_set.find_syntax_plain_text())
+}
+
+// loading the bundled grammars takes a moment, so they are only loaded once
+fn assets() -> &'static (SyntaxSet, Theme) {
+    static ASSETS: OnceLock<(SyntaxSet, Theme)> = OnceLock::new();
+    ASSETS.get_or_init(|| {
+        let mut themes = ThemeSet::load_defaults();
+        let theme = themes.themes.remove(THEME).unwrap_or_default();
+        (SyntaxSet::load_defaults_newlines(), theme)
+    })
+}